@@ -1,14 +1,38 @@
 use rltk::RGB;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
 };
 
 use crate::{
-    map::{Map, RaycastMode},
+    constants::TURN_ENERGY,
+    ecs::{entity::EntityId, world::World},
+    fov,
+    lighting::LightMap,
+    map::Map,
     vectors::Vector,
 };
 
+/// Registers the component types with the world, components which may be duplicated are registered as cloneable
+/// and components which are toggled often are kept in sparse storage
+/// `BlocksTile` is not cloneable, a clone lands on the tile of its original which is already blocked
+pub fn register_components(world: &mut World) {
+    world
+        .register_cloneable::<Position>()
+        .register_cloneable::<Named>()
+        .register_cloneable::<Renderer>()
+        .register_cloneable::<Viewshed>()
+        .register_cloneable::<Lifetime>()
+        .register_cloneable::<Actor>()
+        .register_cloneable::<LightSource>()
+        .register_sparse::<Debug>()
+        .register_sparse::<WantsToMove>()
+        .register_component::<BlocksTile>()
+        .register_component::<Camera>()
+        .register_component::<Player>();
+}
+
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Position {
     position: Vector,
@@ -31,20 +55,52 @@ impl Position {
         self.priority
     }
 
-    pub fn try_move(&mut self, map: &Map, delta: Vector) -> bool {
+    /// Moves without checking the map, the caller is responsible for keeping the occupancy index in sync
+    pub fn place(&mut self, position: Vector) {
+        self.position = position;
+    }
+
+    pub fn try_move(&mut self, map: &Map, delta: Vector) -> MoveResult {
         self.try_set(map, self.position + delta)
     }
 
-    pub fn try_set(&mut self, map: &Map, new_position: Vector) -> bool {
+    pub fn try_set(&mut self, map: &Map, new_position: Vector) -> MoveResult {
         if let Some(tile) = map.get(&new_position) {
             if tile.walkable() {
+                if let Some(blocker) = map.blocker(&new_position) {
+                    return MoveResult::BlockedBy(blocker.clone());
+                }
+
                 self.position = new_position;
 
-                return true;
+                return MoveResult::Moved;
             }
         }
 
-        false
+        MoveResult::Blocked
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MoveResult {
+    Moved,
+    Blocked,
+    BlockedBy(EntityId),
+}
+
+impl MoveResult {
+    pub fn moved(&self) -> bool {
+        matches!(self, MoveResult::Moved)
+    }
+}
+
+/// Marks an entity which occupies its tile, no other entity may move onto it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BlocksTile {}
+
+impl BlocksTile {
+    pub fn new() -> Self {
+        BlocksTile {}
     }
 }
 
@@ -113,6 +169,7 @@ impl Debug {
     }
 }
 
+#[derive(Clone)]
 pub struct Named {
     pub name: String,
 }
@@ -123,6 +180,7 @@ impl Named {
     }
 }
 
+#[derive(Clone)]
 pub struct Renderer {
     glyph: rltk::FontCharType,
     fg: Option<RGB>,
@@ -163,6 +221,7 @@ impl Player {
     }
 }
 
+#[derive(Clone)]
 pub struct Viewshed {
     pub view_distance: f32,
     dirty: bool,
@@ -187,50 +246,26 @@ impl Viewshed {
     }
 
     /// Update the viewshed, returns true iff the view was recalculated
-    pub fn update(&mut self, map: &mut Map, center: Vector, mark_discovered: bool, tick: Option<usize>) -> bool {
+    /// With a light map only tiles in line of sight which are lit well enough are visible, the viewer's own tile always is
+    pub fn update(&mut self, map: &mut Map, center: Vector, lights: Option<&LightMap>, mark_discovered: bool, tick: Option<usize>) -> bool {
         if !self.dirty {
             return false;
         }
 
         self.dirty = false;
-        self.visible = HashSet::new();
-
-        let (top, bottom, left, right) = (
-            (center.y as f32 - self.view_distance).floor() as i32,
-            (center.y as f32 + self.view_distance).ceil() as i32,
-            (center.x as f32 - self.view_distance).floor() as i32,
-            (center.x as f32 + self.view_distance).ceil() as i32,
-        );
-
-        for x in left..=right {
-            for y in top..=bottom {
-                let pos = Vector::new(x, y);
-
-                let dist = Vector::distance(&center, &pos);
-
-                if dist <= self.view_distance {
-                    // We are in the visiblity circle
-                    let result = map.raycast(center, pos, RaycastMode::Visibility);
-
-                    if let Some(hit_pos) = result.hit_position() {
-                        if pos == hit_pos {
-                            if let Some(tile) = map.get_mut(&pos) {
-                                self.visible.insert(pos);
-    
-                                if mark_discovered {
-                                    tile.discover();
-                                    tile.see(tick.expect("a valid tick to set visible"));
-                                }
-                            }
-                        }
-                    } else if let Some(tile) = map.get_mut(&pos) {
-                        self.visible.insert(pos);
-
-                        if mark_discovered {
-                            tile.discover();
-                            tile.see(tick.expect("a valid tick to set visible"));
-                        }
-                    }
+        self.visible = fov::field_of_view(map, center, self.view_distance);
+
+        if let Some(lights) = lights {
+            self.visible.retain(|position| *position == center || lights.lit(position));
+        }
+
+        if mark_discovered {
+            let tick = tick.expect("a valid tick to set visible");
+
+            for position in self.visible.iter() {
+                if let Some(tile) = map.get_mut(position) {
+                    tile.discover();
+                    tile.see(tick);
                 }
             }
         }
@@ -245,4 +280,82 @@ impl Viewshed {
     pub fn visible(&self) -> HashSet<Vector> {
         self.visible.clone()
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Vector> {
+        self.visible.iter()
+    }
+}
+
+/// Lights up the tiles around the entity, the light fades with distance and through opaque tiles
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct LightSource {
+    pub radius: f32,
+    pub color: (u8, u8, u8),
+    pub intensity: u8,
+}
+
+impl LightSource {
+    pub fn new(radius: f32, color: (u8, u8, u8), intensity: u8) -> Self {
+        LightSource { radius, color, intensity }
+    }
+}
+
+/// Despawns the entity once the given number of ticks have passed
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Lifetime {
+    remaining: usize,
+}
+
+impl Lifetime {
+    pub fn new(ticks: usize) -> Self {
+        Lifetime { remaining: ticks }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Counts down a tick, returns true once the lifetime has run out
+    pub fn tick(&mut self) -> bool {
+        self.remaining = self.remaining.saturating_sub(1);
+        self.remaining == 0
+    }
+}
+
+/// An entity which takes turns, it gains `speed` energy every game tick and may act once it has `TURN_ENERGY`
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Actor {
+    pub speed: i32,
+    pub energy: i32,
+}
+
+impl Actor {
+    pub fn new(speed: i32) -> Self {
+        Actor { speed, energy: 0 }
+    }
+
+    pub fn gain(&mut self) {
+        self.energy += self.speed;
+    }
+
+    pub fn ready(&self) -> bool {
+        self.energy >= TURN_ENERGY
+    }
+
+    /// Spends the energy an action costs, faster actions cost less
+    pub fn spend(&mut self, cost: i32) {
+        self.energy -= cost;
+    }
+}
+
+/// An intent to move by `delta`, resolved and removed by the `MovementSystem`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WantsToMove {
+    pub delta: Vector,
+}
+
+impl WantsToMove {
+    pub fn new(delta: Vector) -> Self {
+        WantsToMove { delta }
+    }
 }
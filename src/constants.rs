@@ -3,14 +3,36 @@ use crate::{vectors::Vector};
 // Map
 pub const MAP_SIZE: (usize, usize) = (100, 100);
 
+// Dungeon
+pub const DUNGEON_DEPTH: usize = 10;
+pub const VAULTS_PER_LEVEL: usize = 2;
+
+// Lighting
+pub const AMBIENT_LIGHT: u8 = 0;
+pub const MIN_VISIBLE_LIGHT: u8 = 16;
+pub const TORCH_RADIUS: f32 = 8.5;
+pub const TORCH_COLOR: (u8, u8, u8) = (255, 200, 130);
+
+// Scenes
+pub const DUNGEON_SCENE: &str = "dungeon";
+
+// Setup
+pub const DEFAULT_PLAYER_NAME: &str = "Hazel";
+
+// Tags
+pub const PLAYER_TAG: &str = "player";
+
+// Turns
+pub const TURN_ENERGY: i32 = 100;
+pub const ACTION_COST: i32 = 100;
+pub const PLAYER_SPEED: i32 = 100;
+
 // Glyphs
 pub const PLAYER_GLYPH: char = '@';
 
 // Colors
 pub const PLAYER_COLOR: (u8, u8, u8) = rltk::YELLOW;
 pub const UI_COLOR: (u8, u8, u8) = rltk::GRAY90;
-pub const TERRAIN_COLOR_VISIBLE: (u8, u8, u8) = (200, 200, 100);
-pub const TERRAIN_COLOR_DISCOVERED: (u8, u8, u8) = rltk::GRAY30;
 
 pub const BACKGROUND_COLOR: (u8, u8, u8) = rltk::GRAY4;
 
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use rltk::RandomNumberGenerator;
+
+use crate::{components::{Actor, Position, Viewshed}, constants, entities, ecs::{entity::EntityId, world::World, ECSError}, map::{Map, Stairs, Tile}, mapgen::{Connectivity, GeneratedMap, GeneratorKind, MapLayout}, spatial::SpatialIndex, systems::{TickInfo, TurnScheduler}, vectors::Vector};
+
+/// A level which is not being played, its entities are kept in a world of their own until the player returns
+pub struct Level {
+    map: Map,
+    layout: MapLayout,
+    entities: World,
+}
+
+/// Every level of the dungeon, the current level's `Map` and `MapLayout` are resources on the world and not stored here
+pub struct Dungeon {
+    depth: usize,
+    seed: u64,
+    generator: GeneratorKind,
+    connectivity: Connectivity,
+    levels: HashMap<usize, Level>,
+}
+
+impl Dungeon {
+    pub fn new(seed: u64, generator: GeneratorKind, connectivity: Connectivity) -> Self {
+        Dungeon { depth: 0, seed, generator, connectivity, levels: HashMap::new() }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Whether the level at the depth was visited and left
+    pub fn has_level(&self, depth: usize) -> bool {
+        self.levels.contains_key(&depth)
+    }
+
+    /// Every level gets its own seed so levels come out the same no matter in which order they are visited
+    fn level_seed(&self, depth: usize) -> u64 {
+        self.seed ^ (depth as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }
+
+    /// Builds the level at the depth with an up staircase on its spawn and a down staircase as far away as possible
+    pub fn generate(&self, depth: usize) -> (Map, MapLayout) {
+        let mut rng = RandomNumberGenerator::seeded(self.level_seed(depth));
+
+        let GeneratedMap { mut map, mut layout } = self.generator.build(constants::MAP_SIZE.0, constants::MAP_SIZE.1, self.connectivity, &mut rng);
+
+        if depth > 0 {
+            let spawn = layout.spawn;
+            place_stairs(&mut map, &mut layout, spawn, Stairs::Up);
+        }
+
+        if depth + 1 < constants::DUNGEON_DEPTH {
+            let regions = map.regions();
+
+            // Doors belong to regions and prefabs are spawned later, neither may be replaced by the stairs
+            let furthest = regions.region(&layout.spawn).and_then(|main| {
+                regions.tiles(main)
+                    .filter(|position| map.get(position).is_some_and(|tile| tile.walkable()) && !layout.prefabs.iter().any(|(prefab, _)| prefab == position))
+                    .max_by(|a, b| Vector::distance(&layout.spawn, a).total_cmp(&Vector::distance(&layout.spawn, b)))
+            });
+
+            if let Some(furthest) = furthest {
+                place_stairs(&mut map, &mut layout, furthest, Stairs::Down);
+            }
+        }
+
+        (map, layout)
+    }
+
+    fn take_level(&mut self, depth: usize) -> Level {
+        self.levels.remove(&depth).unwrap_or_else(|| {
+            let (map, layout) = self.generate(depth);
+
+            Level { map, layout, entities: World::new() }
+        })
+    }
+}
+
+fn place_stairs(map: &mut Map, layout: &mut MapLayout, position: Vector, direction: Stairs) {
+    if let Some(tile) = map.get_mut(&position) {
+        *tile = Tile::stairs(direction);
+    }
+
+    match direction {
+        Stairs::Up => layout.up_stairs = Some(position),
+        Stairs::Down => layout.down_stairs = Some(position),
+    }
+
+    layout.spawn_points.retain(|point| *point != position);
+}
+
+/// Takes the player up or down the stairs they are standing on, returns false if there are no such stairs
+/// The level being left is stored along with every entity on it except the player
+pub fn travel(world: &mut World, direction: Stairs) -> Result<bool, ECSError> {
+    let player = world.tagged(constants::PLAYER_TAG).first().cloned().ok_or(ECSError::CouldNotRetrieve)?;
+    let position = world.get_component::<Position>(&player).ok_or(ECSError::CouldNotRetrieve)?.coords();
+
+    let on_stairs = world.get_resource::<Map>().and_then(|map| map.get(&position).and_then(|tile| tile.get_stairs())) == Some(direction);
+
+    if !on_stairs {
+        return Ok(false);
+    }
+
+    let mut dungeon = world.remove_resource::<Dungeon>().ok_or(ECSError::CouldNotRetrieve)?;
+
+    let target = match direction {
+        Stairs::Up => dungeon.depth.checked_sub(1),
+        Stairs::Down => Some(dungeon.depth + 1),
+    };
+
+    let Some(target) = target else {
+        world.insert_resource(dungeon)?;
+        return Ok(false);
+    };
+
+    // The dungeon goes back on the world whether or not the levels could be swapped
+    let arrival = swap_levels(world, &mut dungeon, target, &player, direction);
+    world.insert_resource(dungeon)?;
+    let arrival = arrival?;
+
+    if let Some(mut position) = world.get_component_mut::<Position>(&player) {
+        position.place(arrival);
+    }
+
+    if let Some(mut viewshed) = world.get_component_mut::<Viewshed>(&player) {
+        viewshed.mark_dirty();
+    }
+
+    if let Some(mut actor) = world.get_component_mut::<Actor>(&player) {
+        actor.spend(constants::ACTION_COST);
+    }
+
+    // The entities given a turn were left behind on the other level
+    if let (Some(mut scheduler), Some(mut tick_info)) = (world.get_resource_mut::<TurnScheduler>(), world.get_resource_mut::<TickInfo>()) {
+        scheduler.clear_acting();
+        scheduler.end_player_turn(&mut tick_info);
+    }
+
+    reindex(world);
+
+    Ok(true)
+}
+
+/// Stores the current level along with every entity except the player and puts the target level in its place, returning where the player arrives
+/// When an entity can not be moved everything already moved is moved back and the world is left on the level it was on
+fn swap_levels(world: &mut World, dungeon: &mut Dungeon, target: usize, player: &EntityId, direction: Stairs) -> Result<Vector, ECSError> {
+    let fresh = !dungeon.has_level(target);
+    let mut level = dungeon.take_level(target);
+
+    // A level's prefabs are spawned when it is first entered, afterwards they are kept with its entities
+    // Should spawning fail the fresh level is dropped and generated again on the next attempt
+    if fresh {
+        entities::spawn_prefabs(&mut level.entities, &level.layout.prefabs)?;
+    }
+
+    let mut leaving = World::new();
+
+    if let Err(error) = transfer_all(world, &mut leaving, Some(player)) {
+        dungeon.levels.insert(target, level);
+        return Err(error);
+    }
+
+    if let Err(error) = transfer_all(&mut level.entities, world, None) {
+        dungeon.levels.insert(target, level);
+        transfer_all(&mut leaving, world, None)?;
+        return Err(error);
+    }
+
+    let Level { map, layout, .. } = level;
+
+    // Going down lands on the up staircase of the level below and the other way around
+    let arrival = match direction {
+        Stairs::Up => layout.down_stairs,
+        Stairs::Down => layout.up_stairs,
+    }.unwrap_or(layout.spawn);
+
+    // The map was checked for stairs before travelling so there is always one to replace, the layout is removed first so inserting can not fail
+    let map = std::mem::replace(&mut *world.get_resource_mut::<Map>().expect("the map being left"), map);
+    let previous = world.remove_resource::<MapLayout>().unwrap_or_default();
+    world.insert_resource(layout)?;
+
+    dungeon.levels.insert(dungeon.depth, Level { map, layout: previous, entities: leaving });
+    dungeon.depth = target;
+
+    Ok(arrival)
+}
+
+/// Moves every entity except the one kept into another world, the entities already moved are moved back when one can not be
+fn transfer_all(from: &mut World, to: &mut World, keep: Option<&EntityId>) -> Result<(), ECSError> {
+    let ids: Vec<EntityId> = from.iter().filter_map(|entity| entity.id().cloned()).filter(|id| Some(id) != keep).collect();
+    let mut moved = Vec::new();
+
+    for id in ids {
+        match from.transfer(&id, to) {
+            Ok(new_id) => moved.push(new_id),
+            Err(error) => {
+                for id in moved {
+                    to.transfer(&id, from)?;
+                }
+
+                return Err(error);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Rebuilds the map's occupancy index and the spatial index from scratch
+pub fn reindex(world: &World) {
+    if let Some(mut map) = world.get_resource_mut::<Map>() {
+        map.index_blockers(world);
+    }
+
+    if let Some(mut index) = world.get_resource_mut::<SpatialIndex>() {
+        index.index(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{components::{Actor, Position}, constants, ecs::{entity::Entity, system::System, world::World}, map::{Map, Stairs}, mapgen::MapLayout, setup::GameSetup, systems::{InitiativeSystem, TurnScheduler}};
+
+    use super::{travel, Dungeon};
+
+    fn stand_on(world: &mut World, stairs: Stairs) {
+        let layout = world.get_resource::<MapLayout>().unwrap().clone();
+        let target = match stairs {
+            Stairs::Up => layout.up_stairs,
+            Stairs::Down => layout.down_stairs,
+        }.unwrap();
+
+        let player = world.tagged(constants::PLAYER_TAG)[0].clone();
+        world.get_component_mut::<Position>(&player).unwrap().place(target);
+    }
+
+    #[test]
+    fn test_levels_are_kept_between_visits() {
+        let mut world = World::new();
+        GameSetup::default().with_seed(11).build(&mut world).unwrap();
+
+        assert!(!travel(&mut world, Stairs::Up).unwrap());
+
+        let spawn = world.get_resource::<MapLayout>().unwrap().spawn;
+        world.get_resource_mut::<Map>().unwrap().get_mut(&spawn).unwrap().discover();
+
+        stand_on(&mut world, Stairs::Down);
+        assert!(travel(&mut world, Stairs::Down).unwrap());
+        assert_eq!(world.get_resource::<Dungeon>().unwrap().depth(), 1);
+
+        let up_stairs = world.get_resource::<MapLayout>().unwrap().up_stairs.unwrap();
+        let player = world.tagged(constants::PLAYER_TAG)[0].clone();
+        assert_eq!(world.get_component::<Position>(&player).unwrap().coords(), up_stairs);
+
+        let marker = world.insert(Entity::new().insert_component(Position::new(up_stairs.x, up_stairs.y, 0)).unwrap().insert_component(Actor::new(constants::ACTION_COST)).unwrap().build()).unwrap();
+        world.tag(&marker, "marker");
+
+        InitiativeSystem::new().execute(&world);
+        assert!(world.get_resource::<TurnScheduler>().unwrap().is_acting(&marker));
+
+        assert!(travel(&mut world, Stairs::Up).unwrap());
+        assert!(world.get_resource::<TurnScheduler>().unwrap().acting().is_empty());
+        assert_eq!(world.get_resource::<Dungeon>().unwrap().depth(), 0);
+        assert!(world.tagged("marker").is_empty());
+        assert!(world.get_resource::<Map>().unwrap().get(&spawn).unwrap().discovered());
+
+        let down_stairs = world.get_resource::<MapLayout>().unwrap().down_stairs.unwrap();
+        assert_eq!(world.get_component::<Position>(&player).unwrap().coords(), down_stairs);
+
+        assert!(travel(&mut world, Stairs::Down).unwrap());
+        assert_eq!(world.tagged("marker").len(), 1);
+    }
+}
@@ -10,13 +10,24 @@ impl <T: Ord> SortedVec<T> {
         SortedVec { data: Vec::new() }
     }
 
-    pub fn push(&mut self, item: T) {
+    /// Inserts the item in order, returning the index it was inserted at or None if it was already present
+    pub fn push(&mut self, item: T) -> Option<usize> {
         let index = match self.data.binary_search(&item) {
-            Ok(_) => return,
+            Ok(_) => return None,
             Err(index) => index,
         };
 
         self.data.insert(index, item);
+
+        Some(index)
+    }
+
+    pub fn remove(&mut self, item: &T) -> Option<usize> {
+        let index = self.data.binary_search(item).ok()?;
+
+        self.data.remove(index);
+
+        Some(index)
     }
 
     pub fn len(&self) -> usize {
@@ -73,8 +84,17 @@ impl Archetype {
     }
 
     pub fn add_type_id(&mut self, type_id: TypeId, name: String) -> &mut Self {
-        self.types.push(type_id);
-        self.names.push(name);
+        if let Some(index) = self.types.push(type_id) {
+            self.names.insert(index, name);
+        }
+
+        self
+    }
+
+    pub fn remove_type_id(&mut self, type_id: &TypeId) -> &mut Self {
+        if let Some(index) = self.types.remove(type_id) {
+            self.names.remove(index);
+        }
 
         self
     }
@@ -102,6 +122,10 @@ impl Archetype {
         self.has_type_id(&TypeId::of::<T>())
     }
 
+    pub fn type_ids(&self) -> &[TypeId] {
+        &self.types.data
+    }
+
     pub fn names(&self) -> Vec<String> {
         self.names.clone()
     }
@@ -132,7 +156,7 @@ macro_rules! archetype {
 
 #[cfg(test)]
 mod tests {
-    use super::SortedVec;
+    use super::{SortedVec, Archetype};
 
     #[test]
     fn test_sorted_vec_push() {
@@ -184,4 +208,22 @@ mod tests {
         assert!(s3.contains(&s1));
         assert!(s3.contains(&s2));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_archetype_order_independent() {
+        let mut a = Archetype::new();
+        a.add::<u8>().add::<u16>().add::<u32>();
+
+        let mut b = Archetype::new();
+        b.add::<u32>().add::<u8>().add::<u16>();
+
+        assert_eq!(a, b);
+        assert_eq!(a.to_string(), b.to_string());
+
+        b.remove_type_id(&std::any::TypeId::of::<u16>());
+
+        assert!(!b.has::<u16>());
+        assert_eq!(b.len(), 2);
+        assert_eq!(b.names().len(), 2);
+    }
+}
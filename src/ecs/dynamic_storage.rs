@@ -70,6 +70,11 @@ impl DynamicCell {
         DynamicCell { data: UnsafeCell::new(Box::new(data)), reference_state_cell: Cell::new(ReferenceState::None) }
     }
 
+    /// Whether a mutable reference to the value is alive, reading the value now would panic
+    pub fn borrowed_mut(&self) -> bool {
+        matches!(self.reference_state_cell.get(), ReferenceState::Mutable)
+    }
+
     pub fn get<T: Any>(&self) -> Option<DynamicRef<'_, T>> {
         let value = unsafe {
             (**self.data.get()) // Convert data in UnsafeCell to `dyn Any`
@@ -88,6 +93,11 @@ impl DynamicCell {
         DynamicRefMut::new(value, &self.reference_state_cell)
     }
 
+    /// Takes the value out of the cell, the cell is consumed even if it does not hold a `T`
+    pub fn into_inner<T: Any>(self) -> Option<T> {
+        self.data.into_inner().downcast::<T>().ok().map(|value| *value)
+    }
+
     pub unsafe fn get_unchecked<T: Any>(&self) -> &T {
         (**self.data.get()).downcast_ref_unchecked::<T>()
     }
@@ -186,6 +196,24 @@ impl DynamicStore {
         }
     }
 
+    pub fn insert_cell(&mut self, type_id: TypeId, cell: DynamicCell) -> Result<&mut Self, ECSError> {
+        if let Entry::Vacant(e) = self.data.entry(type_id) {
+            e.insert(cell);
+
+            Ok(self)
+        } else {
+            Err(ECSError::DataAlreadyExists)
+        }
+    }
+
+    pub fn remove<T: Any>(&mut self) -> Option<T> {
+        self.data.remove(&TypeId::of::<T>())?.into_inner::<T>()
+    }
+
+    pub fn remove_type_id(&mut self, type_id: &TypeId) -> Option<DynamicCell> {
+        self.data.remove(type_id)
+    }
+
     pub fn has_type_id(&self, type_id: &TypeId) -> bool {
         self.data.contains_key(type_id)
     }
@@ -194,6 +222,10 @@ impl DynamicStore {
         self.data.contains_key(&TypeId::of::<T>())
     }
 
+    pub fn get_cell_type_id(&self, type_id: &TypeId) -> Option<&DynamicCell> {
+        self.data.get(type_id)
+    }
+
     #[inline]
     fn get_cell<T: Any>(&self) -> Option<&DynamicCell> {
         self.data.get(&TypeId::of::<T>())
@@ -206,4 +238,8 @@ impl DynamicStore {
     pub fn get_mut<T: Any>(&self) -> Option<DynamicRefMut<'_, T>> {
         self.get_cell::<T>()?.get_mut::<T>()
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&TypeId, &DynamicCell)> {
+        self.data.iter()
+    }
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use std::{any::{Any, TypeId}, fmt::Display};
 
-use super::{archetype::Archetype, dynamic_storage::{DynamicStore, DynamicRef, DynamicRefMut}, ECSError};
+use super::{archetype::Archetype, dynamic_storage::{DynamicStore, DynamicRef, DynamicRefMut, DynamicCell}, ECSError};
 
 #[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub struct EntityId {
@@ -44,6 +44,12 @@ impl EntityBuilder {
         self.archetype.add::<T>();
         Ok(self)
     }
+
+    pub fn insert_cell(mut self, type_id: TypeId, name: String, cell: DynamicCell) -> Result<Self, ECSError> {
+        self.components.insert_cell(type_id, cell)?;
+        self.archetype.add_type_id(type_id, name);
+        Ok(self)
+    }
 }
 
 pub struct Entity {
@@ -81,6 +87,10 @@ impl Entity {
         self.components.get_mut::<T>()
     }
 
+    pub fn components(&self) -> impl Iterator<Item = (&TypeId, &DynamicCell)> {
+        self.components.iter()
+    }
+
     pub (super) fn try_set_id(&mut self, id: EntityId) -> Result<(), ECSError> {
         match self.id {
             Some(_) => Err(ECSError::AlreadyInserted),
@@ -88,6 +98,22 @@ impl Entity {
         }
     }
 
+    pub (super) fn insert_cell(&mut self, type_id: TypeId, name: String, cell: DynamicCell) -> Result<&mut Self, ECSError> {
+        self.components.insert_cell(type_id, cell)?;
+        self.archetype.add_type_id(type_id, name);
+        Ok(self)
+    }
+
+    pub (super) fn remove_cell(&mut self, type_id: &TypeId) -> Option<DynamicCell> {
+        let cell = self.components.remove_type_id(type_id)?;
+        self.archetype.remove_type_id(type_id);
+        Some(cell)
+    }
+
+    pub (super) fn clear_id(&mut self) {
+        self.id = None;
+    }
+
     pub fn id(&self) -> Option<&EntityId> {
         self.id.as_ref()
     }
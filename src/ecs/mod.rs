@@ -4,6 +4,9 @@ pub mod system;
 pub mod dynamic_storage;
 pub mod entity;
 pub mod archetype;
+pub mod registry;
+pub mod tags;
+pub mod sparse_set;
 
 #[derive(Debug)]
 pub enum ECSError {
@@ -12,4 +15,5 @@ pub enum ECSError {
     AlreadyInserted,
     InvalidInsertionIndex(usize),
     CouldNotRetrieve,
+    AlreadyBorrowed,
 }
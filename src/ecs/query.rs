@@ -46,6 +46,19 @@ impl Query {
         self.includes.iter().all(|ty| { archetype.has_type_id(ty) }) && !self.excludes.iter().any(|ty| { archetype.has_type_id(ty) })
     }
 
+    /// Matches the archetype against the types which are not sparse, those are checked by `matches_sparse`
+    pub fn matches_tables<F: Fn(&TypeId) -> bool>(&self, archetype: &Archetype, is_sparse: F) -> bool {
+        self.includes.iter().all(|ty| { is_sparse(ty) || archetype.has_type_id(ty) }) && !self.excludes.iter().any(|ty| { !is_sparse(ty) && archetype.has_type_id(ty) })
+    }
+
+    pub fn matches_sparse<F: Fn(&TypeId) -> bool, H: Fn(&TypeId) -> bool>(&self, is_sparse: F, has: H) -> bool {
+        self.includes.iter().all(|ty| { !is_sparse(ty) || has(ty) }) && !self.excludes.iter().any(|ty| { is_sparse(ty) && has(ty) })
+    }
+
+    pub fn has_sparse<F: Fn(&TypeId) -> bool>(&self, is_sparse: F) -> bool {
+        self.includes.iter().chain(self.excludes.iter()).any(is_sparse)
+    }
+
     pub fn join(&mut self, other: Query) -> &mut Self {
         for include in other.includes {
             self.includes.push(include);
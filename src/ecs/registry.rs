@@ -0,0 +1,124 @@
+use std::{any::{Any, TypeId, type_name}, collections::HashMap};
+
+use super::{dynamic_storage::DynamicCell, ECSError};
+
+type CloneFn = fn(&DynamicCell) -> Option<DynamicCell>;
+
+/// Where the components of a type are kept
+/// Table:  Stored on the entity, the component is part of its archetype
+/// Sparse: Stored in a sparse set on the world, adding or removing it does not move the entity between archetypes
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum StorageKind {
+    #[default]
+    Table,
+    Sparse,
+}
+
+#[derive(Clone)]
+pub struct ComponentInfo {
+    name: String,
+    clone: Option<CloneFn>,
+    storage: StorageKind,
+}
+
+impl ComponentInfo {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn storage(&self) -> StorageKind {
+        self.storage
+    }
+
+    pub fn cloneable(&self) -> bool {
+        self.clone.is_some()
+    }
+}
+
+/// Stores per component type information which can not be recovered from a `Box<dyn Any>`
+#[derive(Default, Clone)]
+pub struct ComponentRegistry {
+    components: HashMap<TypeId, ComponentInfo>,
+}
+
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        ComponentRegistry { components: HashMap::new() }
+    }
+
+    pub fn register<T: Any>(&mut self) -> &mut Self {
+        self.components.entry(TypeId::of::<T>()).or_insert_with(|| ComponentInfo {
+            name: type_name::<T>().to_owned(),
+            clone: None,
+            storage: StorageKind::Table,
+        });
+
+        self
+    }
+
+    pub fn register_cloneable<T: Any + Clone>(&mut self) -> &mut Self {
+        self.register::<T>();
+
+        if let Some(info) = self.components.get_mut(&TypeId::of::<T>()) {
+            info.clone = Some(clone_cell::<T>);
+        }
+
+        self
+    }
+
+    pub fn register_sparse<T: Any>(&mut self) -> &mut Self {
+        self.register::<T>();
+
+        if let Some(info) = self.components.get_mut(&TypeId::of::<T>()) {
+            info.storage = StorageKind::Sparse;
+        }
+
+        self
+    }
+
+    pub fn insert_info(&mut self, type_id: TypeId, info: ComponentInfo) {
+        self.components.entry(type_id).or_insert(info);
+    }
+
+    pub fn get(&self, type_id: &TypeId) -> Option<&ComponentInfo> {
+        self.components.get(type_id)
+    }
+
+    pub fn is_cloneable(&self, type_id: &TypeId) -> bool {
+        self.get(type_id).is_some_and(|info| info.cloneable())
+    }
+
+    pub fn storage(&self, type_id: &TypeId) -> StorageKind {
+        self.get(type_id).map(|info| info.storage()).unwrap_or_default()
+    }
+
+    pub fn is_sparse(&self, type_id: &TypeId) -> bool {
+        self.storage(type_id) == StorageKind::Sparse
+    }
+
+    pub fn name(&self, type_id: &TypeId) -> String {
+        match self.get(type_id) {
+            Some(info) => info.name().to_owned(),
+            None => format!("{:?}", type_id),
+        }
+    }
+
+    /// None if the component is not cloneable, an error if it can not be read because it is borrowed mutably
+    pub fn clone_cell(&self, type_id: &TypeId, cell: &DynamicCell) -> Result<Option<DynamicCell>, ECSError> {
+        let Some(clone) = self.get(type_id).and_then(|info| info.clone) else {
+            return Ok(None);
+        };
+
+        if cell.borrowed_mut() {
+            return Err(ECSError::AlreadyBorrowed);
+        }
+
+        clone(cell).map(Some).ok_or(ECSError::CouldNotRetrieve)
+    }
+}
+
+fn clone_cell<T: Any + Clone>(cell: &DynamicCell) -> Option<DynamicCell> {
+    let value = cell.get::<T>()?;
+
+    Some(DynamicCell::new((*value).clone()))
+}
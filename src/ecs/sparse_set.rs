@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use super::{dynamic_storage::DynamicCell, entity::EntityId, ECSError};
+
+/// Stores a single component type outside of the archetype tables, adding or removing one never moves the entity
+#[derive(Default)]
+pub struct SparseSet {
+    sparse: HashMap<EntityId, usize>,
+    dense: Vec<(EntityId, DynamicCell)>,
+}
+
+impl SparseSet {
+    pub fn new() -> Self {
+        SparseSet { sparse: HashMap::new(), dense: Vec::new() }
+    }
+
+    pub fn insert(&mut self, id: EntityId, cell: DynamicCell) -> Result<&mut Self, ECSError> {
+        if self.sparse.contains_key(&id) {
+            return Err(ECSError::DataAlreadyExists);
+        }
+
+        self.sparse.insert(id.clone(), self.dense.len());
+        self.dense.push((id, cell));
+
+        Ok(self)
+    }
+
+    pub fn remove(&mut self, id: &EntityId) -> Option<DynamicCell> {
+        let index = self.sparse.remove(id)?;
+
+        let (_, cell) = self.dense.swap_remove(index);
+
+        if let Some((moved, _)) = self.dense.get(index) {
+            self.sparse.insert(moved.clone(), index);
+        }
+
+        Some(cell)
+    }
+
+    pub fn get(&self, id: &EntityId) -> Option<&DynamicCell> {
+        let index = self.sparse.get(id)?;
+
+        self.dense.get(*index).map(|(_, cell)| cell)
+    }
+
+    pub fn contains(&self, id: &EntityId) -> bool {
+        self.sparse.contains_key(id)
+    }
+
+    /// Moves the component stored under `old` to `new`, used when an entity changes archetype
+    pub fn rekey(&mut self, old: &EntityId, new: &EntityId) {
+        if let Some(index) = self.sparse.remove(old) {
+            self.dense[index].0 = new.clone();
+            self.sparse.insert(new.clone(), index);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&EntityId, &DynamicCell)> {
+        self.dense.iter().map(|(id, cell)| (id, cell))
+    }
+}
@@ -1,9 +1,31 @@
 use super::world::World;
 
+/// Decides whether a system executes during a given world tick
+/// Always: The system runs every frame
+/// If:     The system runs only when the predicate holds for the world at the time the system is reached
+#[derive(Clone, Copy)]
+pub enum RunCriteria {
+    Always,
+    If(fn(&World) -> bool),
+}
+
+impl RunCriteria {
+    pub fn should_run(&self, world: &World) -> bool {
+        match self {
+            RunCriteria::Always => true,
+            RunCriteria::If(predicate) => predicate(world),
+        }
+    }
+}
+
 pub trait System {
     fn initialize(&self, _world: &World) {
         
     }
+
+    fn run_criteria(&self) -> RunCriteria {
+        RunCriteria::Always
+    }
     
     fn execute(&self, world: &World);
-}
\ No newline at end of file
+}
@@ -0,0 +1,84 @@
+use std::{any::{Any, TypeId}, collections::HashMap};
+
+use super::entity::EntityId;
+
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+pub enum TagKey {
+    Name(String),
+    Type(TypeId),
+}
+
+impl TagKey {
+    pub fn of<T: Any>() -> Self {
+        TagKey::Type(TypeId::of::<T>())
+    }
+}
+
+impl From<&str> for TagKey {
+    fn from(name: &str) -> Self {
+        TagKey::Name(name.to_owned())
+    }
+}
+
+impl From<String> for TagKey {
+    fn from(name: String) -> Self {
+        TagKey::Name(name)
+    }
+}
+
+/// Maps tags to the entities carrying them, a tag may be shared by several entities
+#[derive(Default, Clone)]
+pub struct Tags {
+    tagged: HashMap<TagKey, Vec<EntityId>>,
+}
+
+impl Tags {
+    pub fn new() -> Self {
+        Tags { tagged: HashMap::new() }
+    }
+
+    pub fn tag(&mut self, id: &EntityId, key: TagKey) {
+        let entities = self.tagged.entry(key).or_default();
+
+        if !entities.contains(id) {
+            entities.push(id.clone());
+        }
+    }
+
+    pub fn untag(&mut self, id: &EntityId, key: &TagKey) {
+        if let Some(entities) = self.tagged.get_mut(key) {
+            entities.retain(|other| other != id);
+
+            if entities.is_empty() {
+                self.tagged.remove(key);
+            }
+        }
+    }
+
+    pub fn tagged(&self, key: &TagKey) -> &[EntityId] {
+        match self.tagged.get(key) {
+            Some(entities) => entities,
+            None => &[],
+        }
+    }
+
+    pub fn has_tag(&self, id: &EntityId, key: &TagKey) -> bool {
+        self.tagged(key).contains(id)
+    }
+
+    pub fn keys_of(&self, id: &EntityId) -> Vec<TagKey> {
+        self.tagged.iter().filter(|(_, entities)| entities.contains(id)).map(|(key, _)| key.clone()).collect()
+    }
+
+    /// Removes the entity from every tag
+    pub fn remove_entity(&mut self, id: &EntityId) {
+        self.tagged.retain(|_, entities| {
+            entities.retain(|other| other != id);
+            !entities.is_empty()
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.tagged.clear();
+    }
+}
@@ -1,11 +1,33 @@
-use std::{collections::HashMap, any::{TypeId, Any}, cmp::Ordering};
+use std::{collections::HashMap, any::{TypeId, Any, type_name}, cmp::Ordering, cell::RefCell};
 
-use super::{archetype::Archetype, entity::{Entity, EntityId}, system::System, dynamic_storage::{DynamicStore, DynamicRef, DynamicRefMut}, query::Query, ECSError};
+use super::{archetype::Archetype, entity::{Entity, EntityId}, system::System, dynamic_storage::{DynamicStore, DynamicRef, DynamicRefMut, DynamicCell}, query::Query, registry::{ComponentRegistry, StorageKind}, sparse_set::SparseSet, tags::{Tags, TagKey}, ECSError};
+
+pub struct CloneReport {
+    pub id: EntityId,
+    pub skipped: Vec<String>,
+}
+
+type DeferredCommand = Box<dyn FnOnce(&mut World)>;
+
+/// The error along with the entity and sparse components which could not be inserted
+type InsertFailure = Box<(ECSError, Entity, Vec<(TypeId, DynamicCell)>)>;
+
+/// Called whenever an entity id stops being valid, the new id is given when the entity was only moved to another archetype
+pub type IdHook = fn(&mut World, &EntityId, Option<&EntityId>);
+
+/// Called after an entity is inserted, with all of its table and sparse components in place
+pub type InsertHook = fn(&mut World, &EntityId);
 
 pub struct World {
     entities: HashMap<Archetype, Vec<Option<Entity>>>,
     systems: Vec<(Box<dyn System>, i32)>,
     resources: DynamicStore,
+    registry: ComponentRegistry,
+    tags: Tags,
+    sparse: HashMap<TypeId, SparseSet>,
+    deferred: RefCell<Vec<DeferredCommand>>,
+    id_hooks: Vec<IdHook>,
+    insert_hooks: Vec<InsertHook>,
 }
 
 impl World {
@@ -14,6 +36,12 @@ impl World {
             entities: Default::default(),
             systems: Default::default(),
             resources: Default::default(),
+            registry: Default::default(),
+            tags: Default::default(),
+            sparse: Default::default(),
+            deferred: Default::default(),
+            id_hooks: Default::default(),
+            insert_hooks: Default::default(),
         }
     }
     
@@ -31,24 +59,99 @@ impl World {
         EntityId::new(archetype, entities.len())
     }
 
-    pub fn insert(&mut self, mut entity: Entity) -> Result<EntityId, ECSError> {
-        let id = self.get_next_id(entity.archetype().clone());
+    /// Inserts the entity, any components registered as sparse are moved out of the entity into the world's sparse sets
+    pub fn insert(&mut self, entity: Entity) -> Result<EntityId, ECSError> {
+        self.try_insert(entity, Vec::new(), Vec::new()).map_err(|failure| failure.0)
+    }
 
-        let entry = self.entities.entry(entity.archetype().clone());
+    /// Inserts the entity along with sparse components and tags kept outside of it, the insert hooks run once all of them are in place
+    /// Nothing is inserted when it fails and the entity and sparse components are handed back
+    fn try_insert(&mut self, mut entity: Entity, mut sparse: Vec<(TypeId, DynamicCell)>, tags: Vec<TagKey>) -> Result<EntityId, InsertFailure> {
+        if entity.id().is_some() {
+            return Err(Box::new((ECSError::AlreadyInserted, entity, sparse)));
+        }
 
-        entity.try_set_id(id.clone())?;
+        let sparse_types: Vec<TypeId> = entity.components().map(|(type_id, _)| *type_id).filter(|type_id| self.registry.is_sparse(type_id)).collect();
 
-        let entities = entry.or_default();
+        if sparse.iter().any(|(type_id, _)| entity.has_component_type_id(type_id)) {
+            return Err(Box::new((ECSError::DataAlreadyExists, entity, sparse)));
+        }
+
+        let mut archetype = entity.archetype().clone();
+
+        for type_id in sparse_types.iter() {
+            archetype.remove_type_id(type_id);
+        }
+
+        // Everything which can fail is checked before any component is moved out of the entity
+        let id = self.get_next_id(archetype);
+
+        if sparse_types.iter().chain(sparse.iter().map(|(type_id, _)| type_id)).any(|type_id| self.sparse.get(type_id).is_some_and(|set| set.contains(&id))) {
+            return Err(Box::new((ECSError::DataAlreadyExists, entity, sparse)));
+        }
+
+        sparse.extend(sparse_types.into_iter().filter_map(|type_id| Some((type_id, entity.remove_cell(&type_id)?))));
+
+        let id = match self.insert_table(entity) {
+            Ok(id) => id,
+            Err(failure) => {
+                let (error, entity) = *failure;
+
+                return Err(Box::new((error, entity, sparse)));
+            },
+        };
+
+        // The sets were checked to be free for the id above so this can not fail
+        for (type_id, cell) in sparse {
+            let _ = self.sparse.entry(type_id).or_default().insert(id.clone(), cell);
+        }
+
+        for key in tags {
+            self.tags.tag(&id, key);
+        }
+
+        for hook in self.insert_hooks.clone() {
+            hook(self, &id);
+        }
+
+        Ok(id)
+    }
+
+    /// Hands the entity back when it can not be inserted
+    fn insert_table(&mut self, mut entity: Entity) -> Result<EntityId, Box<(ECSError, Entity)>> {
+        let id = self.get_next_id(entity.archetype().clone());
+
+        if let Err(error) = entity.try_set_id(id.clone()) {
+            return Err(Box::new((error, entity)));
+        }
+
+        let entities = self.entities.entry(entity.archetype().clone()).or_default();
 
         match id.index().cmp(&entities.len()) {
             Ordering::Less => { entities[id.index()] = Some(entity); },
             Ordering::Equal => { entities.insert(id.index(), Some(entity)); },
-            Ordering::Greater => { return Err(ECSError::InvalidInsertionIndex(id.index())); },
+            Ordering::Greater => {
+                entity.clear_id();
+
+                return Err(Box::new((ECSError::InvalidInsertionIndex(id.index()), entity)));
+            },
         }
 
         Ok(id)
     }
 
+    /// Removes every entity, tag, resource, system and hook, registered components are kept
+    pub fn clear(&mut self) {
+        self.entities.clear();
+        self.systems.clear();
+        self.resources = DynamicStore::new();
+        self.tags.clear();
+        self.sparse.clear();
+        self.deferred.borrow_mut().clear();
+        self.id_hooks.clear();
+        self.insert_hooks.clear();
+    }
+
     pub fn get(&self, id: &EntityId) -> Option<&Entity> {
         self.entities.get(id.archetype())?.get(id.index())?.as_ref()
     }
@@ -66,13 +169,21 @@ impl World {
     }
 
     pub fn query_entities<'a>(&'a self, query: &'a Query) -> impl Iterator<Item = &'a Entity> {
-        self.entities.iter().filter_map(|(archetype, entities)| {
-            if query.matches(archetype) {
+        let is_sparse = |type_id: &TypeId| self.registry.is_sparse(type_id);
+        let check_sparse = query.has_sparse(is_sparse);
+
+        self.entities.iter().filter_map(move |(archetype, entities)| {
+            if query.matches_tables(archetype, is_sparse) {
                 Some(entities)
             } else {
                 None
             }
-         }).flat_map(|entities| { entities.iter() }).flatten()
+         }).flat_map(|entities| { entities.iter() }).flatten().filter(move |entity| {
+            !check_sparse || match entity.id() {
+                Some(id) => query.matches_sparse(is_sparse, |type_id| self.has_sparse_type_id(id, type_id)),
+                None => false,
+            }
+         })
     }
 
     pub fn query_one_entity<'a>(&'a self, query: &'a Query) -> Option<&Entity> {
@@ -82,15 +193,256 @@ impl World {
     pub fn remove(&mut self, entity: Entity) -> Option<Entity> {
         let id = entity.id().expect("an inserted entity");
 
-        let list = self.entities.get_mut(id.archetype()).expect("a valid archetype");
-
-        list.remove(id.index())
+        self.despawn(id)
     }
 
     pub fn remove_id(&mut self, id: EntityId) -> Option<Entity> {
-        let list = self.entities.get_mut(id.archetype()).expect("a valid archetype");
+        self.despawn(&id)
+    }
+
+    /// Removes the entity from the world, leaving its slot free for reuse and clearing its tags and sparse components
+    pub fn despawn(&mut self, id: &EntityId) -> Option<Entity> {
+        let entity = self.take(id)?;
+
+        self.tags.remove_entity(id);
+        self.take_sparse(id);
+        self.run_id_hooks(id, None);
+
+        Some(entity)
+    }
+
+    fn take(&mut self, id: &EntityId) -> Option<Entity> {
+        self.entities.get_mut(id.archetype())?.get_mut(id.index())?.take()
+    }
+
+    fn take_sparse(&mut self, id: &EntityId) -> Vec<(TypeId, DynamicCell)> {
+        self.sparse.iter_mut().filter_map(|(type_id, set)| Some((*type_id, set.remove(id)?))).collect()
+    }
+
+    /// Updates everything keyed on an entity id after the entity moved to a different archetype
+    fn rekey(&mut self, old: &EntityId, new: &EntityId) {
+        for key in self.tags.keys_of(old) {
+            self.tags.untag(old, &key);
+            self.tags.tag(new, key);
+        }
+
+        for set in self.sparse.values_mut() {
+            set.rekey(old, new);
+        }
+
+        self.run_id_hooks(old, Some(new));
+    }
+
+    pub fn add_id_hook(&mut self, hook: IdHook) -> &mut Self {
+        self.id_hooks.push(hook);
+        self
+    }
+
+    pub fn add_insert_hook(&mut self, hook: InsertHook) -> &mut Self {
+        self.insert_hooks.push(hook);
+        self
+    }
+
+    fn run_id_hooks(&mut self, old: &EntityId, new: Option<&EntityId>) {
+        for hook in self.id_hooks.clone() {
+            hook(self, old, new);
+        }
+    }
+
+    /// Moves an entity along with all of its components and tags into another world, returning its new id
+    /// The other world's insert hooks see the whole entity, if it can not be inserted there it is put back where it was
+    pub fn transfer(&mut self, id: &EntityId, other: &mut World) -> Result<EntityId, ECSError> {
+        let mut entity = self.take(id).ok_or(ECSError::CouldNotRetrieve)?;
+        let sparse = self.take_sparse(id);
+        let tags = self.tags.keys_of(id);
+
+        for (type_id, _) in sparse.iter() {
+            if let Some(info) = self.registry.get(type_id) {
+                other.registry.insert_info(*type_id, info.clone());
+            }
+        }
+
+        entity.clear_id();
+
+        match other.try_insert(entity, sparse, tags) {
+            Ok(new_id) => {
+                self.tags.remove_entity(id);
+                self.run_id_hooks(id, None);
+
+                Ok(new_id)
+            },
+            Err(failure) => {
+                let (error, entity, sparse) = *failure;
+
+                self.restore(id, entity, sparse)?;
+
+                Err(error)
+            },
+        }
+    }
+
+    /// Puts an entity taken out of its slot back along with its sparse components
+    fn restore(&mut self, id: &EntityId, mut entity: Entity, sparse: Vec<(TypeId, DynamicCell)>) -> Result<(), ECSError> {
+        entity.try_set_id(id.clone())?;
+
+        let slot = self.entities.get_mut(id.archetype()).and_then(|entities| entities.get_mut(id.index())).ok_or(ECSError::InvalidInsertionIndex(id.index()))?;
+        *slot = Some(entity);
+
+        for (type_id, cell) in sparse {
+            self.sparse.entry(type_id).or_default().insert(id.clone(), cell)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a component to an inserted entity
+    /// Table components move the entity to a new archetype and so change its id, sparse components leave the id untouched
+    pub fn add_component<T: Any>(&mut self, id: &EntityId, component: T) -> Result<EntityId, ECSError> {
+        self.attach_cell(id, TypeId::of::<T>(), type_name::<T>().to_owned(), DynamicCell::new(component))
+    }
+
+    /// Removes a component from an inserted entity, returning the entity's possibly changed id
+    pub fn remove_component<T: Any>(&mut self, id: &EntityId) -> Result<EntityId, ECSError> {
+        let type_id = TypeId::of::<T>();
+
+        match self.registry.storage(&type_id) {
+            StorageKind::Sparse => {
+                self.sparse.get_mut(&type_id).and_then(|set| set.remove(id)).ok_or(ECSError::CouldNotRetrieve)?;
+
+                Ok(id.clone())
+            },
+            StorageKind::Table => {
+                if !self.get(id).ok_or(ECSError::CouldNotRetrieve)?.has_component_type_id(&type_id) {
+                    return Err(ECSError::CouldNotRetrieve);
+                }
+
+                let mut entity = self.take(id).ok_or(ECSError::CouldNotRetrieve)?;
+
+                entity.remove_cell(&type_id);
+                entity.clear_id();
+
+                let new_id = self.insert_table(entity).map_err(|failure| failure.0)?;
+                self.rekey(id, &new_id);
+
+                Ok(new_id)
+            }
+        }
+    }
+
+    fn attach_cell(&mut self, id: &EntityId, type_id: TypeId, name: String, cell: DynamicCell) -> Result<EntityId, ECSError> {
+        if self.get(id).ok_or(ECSError::CouldNotRetrieve)?.has_component_type_id(&type_id) {
+            return Err(ECSError::DataAlreadyExists);
+        }
+
+        match self.registry.storage(&type_id) {
+            StorageKind::Sparse => {
+                self.sparse.entry(type_id).or_default().insert(id.clone(), cell)?;
+
+                Ok(id.clone())
+            },
+            StorageKind::Table => {
+                let mut entity = self.take(id).ok_or(ECSError::CouldNotRetrieve)?;
+
+                entity.insert_cell(type_id, name, cell)?;
+                entity.clear_id();
+
+                let new_id = self.insert_table(entity).map_err(|failure| failure.0)?;
+                self.rekey(id, &new_id);
+
+                Ok(new_id)
+            }
+        }
+    }
+
+    pub fn has_component<T: Any>(&self, id: &EntityId) -> bool {
+        let type_id = TypeId::of::<T>();
+
+        self.has_sparse_type_id(id, &type_id) || self.get(id).is_some_and(|entity| entity.has_component_type_id(&type_id))
+    }
+
+    /// Gets a component of an entity regardless of how it is stored
+    pub fn get_component<T: Any>(&self, id: &EntityId) -> Option<DynamicRef<'_, T>> {
+        match self.registry.storage(&TypeId::of::<T>()) {
+            StorageKind::Sparse => self.sparse.get(&TypeId::of::<T>())?.get(id)?.get::<T>(),
+            StorageKind::Table => self.get(id)?.get_component::<T>(),
+        }
+    }
+
+    pub fn get_component_mut<T: Any>(&self, id: &EntityId) -> Option<DynamicRefMut<'_, T>> {
+        match self.registry.storage(&TypeId::of::<T>()) {
+            StorageKind::Sparse => self.sparse.get(&TypeId::of::<T>())?.get(id)?.get_mut::<T>(),
+            StorageKind::Table => self.get(id)?.get_component_mut::<T>(),
+        }
+    }
+
+    fn has_sparse_type_id(&self, id: &EntityId, type_id: &TypeId) -> bool {
+        self.sparse.get(type_id).is_some_and(|set| set.contains(id))
+    }
+
+    pub fn tag<K: Into<TagKey>>(&mut self, id: &EntityId, key: K) -> &mut Self {
+        self.tags.tag(id, key.into());
+        self
+    }
+
+    pub fn untag<K: Into<TagKey>>(&mut self, id: &EntityId, key: K) -> &mut Self {
+        self.tags.untag(id, &key.into());
+        self
+    }
+
+    pub fn tagged<K: Into<TagKey>>(&self, key: K) -> &[EntityId] {
+        self.tags.tagged(&key.into())
+    }
+
+    pub fn tagged_one<K: Into<TagKey>>(&self, key: K) -> Option<&Entity> {
+        self.get(self.tagged(key).first()?)
+    }
+
+    pub fn has_tag<K: Into<TagKey>>(&self, id: &EntityId, key: K) -> bool {
+        self.tags.has_tag(id, &key.into())
+    }
+
+    pub fn register_component<T: Any>(&mut self) -> &mut Self {
+        self.registry.register::<T>();
+        self
+    }
+
+    pub fn register_cloneable<T: Any + Clone>(&mut self) -> &mut Self {
+        self.registry.register_cloneable::<T>();
+        self
+    }
+
+    /// Stores the component in a sparse set rather than the archetype tables, best for components which are added and removed often
+    pub fn register_sparse<T: Any>(&mut self) -> &mut Self {
+        self.registry.register_sparse::<T>();
+        self
+    }
+
+    pub fn registry(&self) -> &ComponentRegistry {
+        &self.registry
+    }
+
+    /// Clones every component registered as cloneable onto a new entity, components which are not are skipped and listed in the report
+    /// Fails without spawning anything if a cloneable component is borrowed mutably
+    pub fn clone_entity(&mut self, id: &EntityId) -> Result<CloneReport, ECSError> {
+        let source = self.get(id).ok_or(ECSError::CouldNotRetrieve)?;
+
+        let mut builder = Entity::new();
+        let mut skipped = Vec::new();
+
+        let sparse = self.sparse.iter().filter_map(|(type_id, set)| Some((type_id, set.get(id)?)));
+
+        for (type_id, cell) in source.components().chain(sparse) {
+            let name = self.registry.name(type_id);
+
+            match self.registry.clone_cell(type_id, cell)? {
+                Some(clone) => { builder = builder.insert_cell(*type_id, name, clone)?; },
+                None => skipped.push(name),
+            }
+        }
+
+        let id = self.insert(builder.build())?;
 
-        list.remove(id.index())
+        Ok(CloneReport { id, skipped })
     }
 
     pub fn add_system(&mut self, system: Box<dyn System>, priority: i32) -> &mut Self {
@@ -111,9 +463,29 @@ impl World {
         self
     }
 
+    pub fn system_count(&self) -> usize {
+        self.systems.len()
+    }
+
     pub fn tick(&self) {
         for (system, _) in self.systems.iter() {
-            system.execute(self)
+            if system.run_criteria().should_run(self) {
+                system.execute(self)
+            }
+        }
+    }
+
+    /// Queues a command which needs mutable access to the world, systems use this to spawn and despawn entities
+    pub fn defer<F: FnOnce(&mut World) + 'static>(&self, command: F) {
+        self.deferred.borrow_mut().push(Box::new(command));
+    }
+
+    /// Runs every deferred command in the order they were queued
+    pub fn apply_deferred(&mut self) {
+        let commands = self.deferred.take();
+
+        for command in commands {
+            command(self);
         }
     }
 
@@ -122,6 +494,10 @@ impl World {
         Ok(self)
     }
 
+    pub fn remove_resource<T: Any>(&mut self) -> Option<T> {
+        self.resources.remove::<T>()
+    }
+
     pub fn has_resource<T: Any>(&self) -> bool {
         self.resources.has::<T>()
     }
@@ -147,4 +523,105 @@ impl Default for World {
     fn default() -> Self {
         World::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::TypeId;
+
+    use crate::{ecs::{dynamic_storage::DynamicCell, entity::Entity, ECSError}, query};
+
+    use super::World;
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Health(i32);
+
+    struct Marker;
+
+    #[derive(PartialEq, Debug)]
+    struct Armor(i32);
+
+    #[test]
+    fn test_clone_entity() {
+        let mut world = World::new();
+        world.register_cloneable::<Health>();
+
+        let id = world.insert(Entity::new().insert_component(Health(5)).unwrap().insert_component(Marker).unwrap().build()).unwrap();
+
+        let report = world.clone_entity(&id).unwrap();
+        let clone = world.get(&report.id).unwrap();
+
+        assert_ne!(report.id, id);
+        assert_eq!(*clone.get_component::<Health>().unwrap(), Health(5));
+        assert!(!clone.has_component::<Marker>());
+        assert_eq!(report.skipped.len(), 1);
+
+        let cell = DynamicCell::new(Health(1));
+        let health = cell.get_mut::<Health>().unwrap();
+        assert!(matches!(world.registry().clone_cell(&TypeId::of::<Health>(), &cell), Err(ECSError::AlreadyBorrowed)));
+        drop(health);
+        assert!(world.registry().clone_cell(&TypeId::of::<Health>(), &cell).unwrap().is_some());
+        assert!(world.registry().clone_cell(&TypeId::of::<Marker>(), &DynamicCell::new(Marker)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_tags_cleared_on_despawn() {
+        let mut world = World::new();
+
+        let a = world.insert(Entity::new().insert_component(Health(1)).unwrap().build()).unwrap();
+        let b = world.insert(Entity::new().insert_component(Health(2)).unwrap().build()).unwrap();
+
+        world.tag(&a, "boss").tag(&b, "boss").tag(&a, "player");
+
+        assert_eq!(world.tagged("boss").len(), 2);
+
+        world.despawn(&a);
+
+        assert_eq!(world.tagged("boss"), std::slice::from_ref(&b));
+        assert!(world.tagged("player").is_empty());
+        assert!(world.get(&b).is_some());
+    }
+
+    #[test]
+    fn test_transfer() {
+        let mut from = World::new();
+        let mut to = World::new();
+
+        let id = from.insert(Entity::new().insert_component(Health(3)).unwrap().build()).unwrap();
+        from.tag(&id, "player");
+
+        let new_id = from.transfer(&id, &mut to).unwrap();
+
+        assert!(from.get(&id).is_none());
+        assert!(from.tagged("player").is_empty());
+        assert_eq!(*to.get(&new_id).unwrap().get_component::<Health>().unwrap(), Health(3));
+        assert_eq!(to.tagged("player"), &[new_id]);
+    }
+
+    #[test]
+    fn test_sparse_components() {
+        let mut world = World::new();
+        world.register_sparse::<Marker>();
+
+        let id = world.insert(Entity::new().insert_component(Health(1)).unwrap().insert_component(Marker).unwrap().build()).unwrap();
+        world.tag(&id, "slime");
+
+        assert!(!id.archetype().has::<Marker>());
+        assert!(world.has_component::<Marker>(&id));
+        assert_eq!(world.query_entities(&query!(Health, Marker)).count(), 1);
+        assert_eq!(world.query_entities(&query!(Health).exclude::<Marker>()).count(), 0);
+
+        let same = world.remove_component::<Marker>(&id).unwrap();
+
+        assert_eq!(same, id);
+        assert_eq!(world.query_entities(&query!(Health, Marker)).count(), 0);
+        assert_eq!(world.query_entities(&query!(Health).exclude::<Marker>()).count(), 1);
+
+        let moved = world.add_component(&id, Armor(2)).unwrap();
+
+        assert_ne!(moved, id);
+        assert!(world.get(&id).is_none());
+        assert_eq!(*world.get_component::<Armor>(&moved).unwrap(), Armor(2));
+        assert_eq!(world.tagged("slime"), &[moved]);
+    }
+}
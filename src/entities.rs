@@ -2,7 +2,8 @@ use rltk::RGB;
 
 use crate::components::*;
 use crate::constants::*;
-use crate::ecs::{ECSError, entity::EntityBuilder};
+use crate::ecs::{ECSError, entity::{Entity, EntityBuilder}, world::World};
+use crate::vectors::Vector;
 
 type BuilderResult<'w> = Result<EntityBuilder, ECSError>;
 
@@ -62,5 +63,50 @@ pub fn player(
     )?
     .insert_component(Camera::new())?
     .insert_component(Player::new())?
-    .insert_component(Viewshed::new(11.5))
+    .insert_component(Viewshed::new(11.5))?
+    .insert_component(LightSource::new(TORCH_RADIUS, TORCH_COLOR, 255))?
+    .insert_component(Actor::new(PLAYER_SPEED))?
+    .insert_component(BlocksTile::new())
+}
+
+pub fn brazier(
+    entity: BuilderResult,
+    x: i32,
+    y: i32,
+) -> BuilderResult {
+    renderable(
+        named(entity, "Brazier".into()),
+        x,
+        y,
+        1,
+        '☼',
+        Some(RGB::named(TORCH_COLOR)),
+        None,
+    )?
+    .insert_component(LightSource::new(TORCH_RADIUS, TORCH_COLOR, 255))?
+    .insert_component(BlocksTile::new())
+}
+
+/// Builds the entity placed by name, as vaults do, None if there is no such prefab
+pub fn prefab<'w>(
+    entity: BuilderResult<'w>,
+    name: &str,
+    x: i32,
+    y: i32,
+) -> Option<BuilderResult<'w>> {
+    match name {
+        "brazier" => Some(brazier(entity, x, y)),
+        _ => None,
+    }
+}
+
+/// Spawns every prefab at its position, unknown names are skipped
+pub fn spawn_prefabs(world: &mut World, prefabs: &[(Vector, String)]) -> Result<(), ECSError> {
+    for (position, name) in prefabs {
+        if let Some(entity) = prefab(Ok(Entity::new()), name, position.x, position.y) {
+            world.insert(entity?.build())?;
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,263 @@
+use std::{cmp::Ordering, collections::{HashMap, HashSet}};
+
+use crate::{map::Map, vectors::Vector};
+
+/// The light a viewer starts out with, every tile subtracts its opaqueness from it as in `Map::raycast`
+const FULL_LIGHT: u8 = 255;
+
+/// The tiles visible from the origin within the radius, using symmetric shadowcasting
+/// Light fades through partially opaque tiles the same way as in `RaycastMode::Visibility`, a tile at least as opaque as the remaining light is seen but blocks everything behind it
+pub fn field_of_view(map: &Map, origin: Vector, radius: f32) -> HashSet<Vector> {
+    let mut visible = HashSet::new();
+
+    cast(map, origin, radius, FULL_LIGHT, &mut |position, _| { visible.insert(position); });
+
+    visible
+}
+
+/// Like `field_of_view` but starting out with the given light, every reached tile maps to the light arriving at it before its own opaqueness is subtracted
+pub fn light_field(map: &Map, origin: Vector, radius: f32, light: u8) -> HashMap<Vector, u8> {
+    let mut lit: HashMap<Vector, u8> = HashMap::new();
+
+    cast(map, origin, radius, light, &mut |position, light| {
+        let entry = lit.entry(position).or_insert(light);
+        *entry = (*entry).max(light);
+    });
+
+    lit
+}
+
+fn cast(map: &Map, origin: Vector, radius: f32, light: u8, visit: &mut dyn FnMut(Vector, u8)) {
+    let Some(tile) = map.get(&origin) else {
+        return;
+    };
+
+    visit(origin, light);
+
+    let light = light.saturating_sub(tile.opaqueness());
+
+    if light == 0 {
+        return;
+    }
+
+    for quadrant in [Quadrant::North, Quadrant::East, Quadrant::South, Quadrant::West] {
+        let mut caster = Caster { map, origin, radius, quadrant, visit: &mut *visit };
+
+        caster.scan(1, Slope::new(-1, 1), Slope::new(1, 1), light);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Quadrant {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Quadrant {
+    /// Turns a row depth and a column within the row into map coordinates
+    fn transform(&self, origin: Vector, depth: i32, column: i32) -> Vector {
+        match self {
+            Quadrant::North => Vector::new(origin.x + column, origin.y - depth),
+            Quadrant::South => Vector::new(origin.x + column, origin.y + depth),
+            Quadrant::East => Vector::new(origin.x + depth, origin.y + column),
+            Quadrant::West => Vector::new(origin.x - depth, origin.y + column),
+        }
+    }
+}
+
+/// A rational slope, kept exact so the symmetry check never suffers from rounding
+#[derive(Clone, Copy, Debug)]
+struct Slope {
+    numerator: i32,
+    denominator: i32,
+}
+
+impl Slope {
+    fn new(numerator: i32, denominator: i32) -> Self {
+        Slope { numerator, denominator }
+    }
+
+    /// The slope through the near left corner of the tile
+    fn of_tile(depth: i32, column: i32) -> Self {
+        Slope::new(2 * column - 1, 2 * depth)
+    }
+
+    fn compare(&self, depth: i32, column: i32) -> Ordering {
+        (column * self.denominator).cmp(&(depth * self.numerator))
+    }
+
+    /// The first column of the row at the depth, rounding half way points up
+    fn min_column(&self, depth: i32) -> i32 {
+        (2 * depth * self.numerator + self.denominator).div_euclid(2 * self.denominator)
+    }
+
+    /// The last column of the row at the depth, rounding half way points down
+    fn max_column(&self, depth: i32) -> i32 {
+        -(-(2 * depth * self.numerator - self.denominator)).div_euclid(2 * self.denominator)
+    }
+}
+
+struct Caster<'c> {
+    map: &'c Map,
+    origin: Vector,
+    radius: f32,
+    quadrant: Quadrant,
+    visit: &'c mut dyn FnMut(Vector, u8),
+}
+
+impl <'c> Caster<'c> {
+    /// Scans the row at the depth between the slopes, splitting it wherever the light passing through changes
+    fn scan(&mut self, depth: i32, start: Slope, end: Slope, light: u8) {
+        if depth as f32 > self.radius {
+            return;
+        }
+
+        let mut start = start;
+        let mut previous: Option<u8> = None;
+
+        for column in start.min_column(depth)..=end.max_column(depth) {
+            let position = self.quadrant.transform(self.origin, depth, column);
+
+            // Tiles outside the map block like walls but are never seen
+            let opaqueness = self.map.get(&position).map_or(FULL_LIGHT, |tile| tile.opaqueness());
+            let passing = light.saturating_sub(opaqueness);
+
+            // Blocking tiles are seen as soon as any part of them is lit, open tiles only when their centre is
+            let symmetric = start.compare(depth, column) != Ordering::Less && end.compare(depth, column) != Ordering::Greater;
+
+            if (passing == 0 || symmetric) && self.map.in_bounds(&position) && Vector::distance(&self.origin, &position) <= self.radius {
+                (self.visit)(position, light);
+            }
+
+            if let Some(previous) = previous {
+                if previous != passing {
+                    if previous > 0 {
+                        self.scan(depth + 1, start, Slope::of_tile(depth, column), previous);
+                    }
+
+                    start = Slope::of_tile(depth, column);
+                }
+            }
+
+            previous = Some(passing);
+        }
+
+        if let Some(previous) = previous.filter(|previous| *previous > 0) {
+            self.scan(depth + 1, start, end, previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+    use rltk::RandomNumberGenerator;
+    use test::Bencher;
+
+    use crate::{map::{Map, RaycastMode, Tile}, mapgen::{Connectivity, GeneratorKind}, vectors::Vector};
+
+    use super::field_of_view;
+
+    const VIEW_DISTANCE: f32 = 11.5;
+
+    #[test]
+    fn test_open_and_walled_views() {
+        let mut map = Map::empty(30, 30);
+        let origin = Vector::new(15, 15);
+
+        let visible = field_of_view(&map, origin, VIEW_DISTANCE);
+        let expected = (0..30).flat_map(|x| (0..30).map(move |y| Vector::new(x, y))).filter(|position| Vector::distance(&origin, position) <= VIEW_DISTANCE).count();
+        assert_eq!(visible.len(), expected);
+
+        *map.get_mut(&Vector::new(17, 15)).unwrap() = Tile::wall();
+        *map.get_mut(&Vector::new(15, 17)).unwrap() = Tile::window();
+
+        let visible = field_of_view(&map, origin, VIEW_DISTANCE);
+        assert!(visible.contains(&Vector::new(17, 15)));
+        assert!(!visible.contains(&Vector::new(18, 15)));
+        assert!(visible.contains(&Vector::new(15, 18)));
+        assert!(!field_of_view(&map, Vector::new(-1, 0), VIEW_DISTANCE).contains(&Vector::new(0, 0)));
+    }
+
+    proptest! {
+        #[test]
+        fn test_field_of_view_is_symmetric(walls in prop::collection::vec((0..16i32, 0..16i32), 0..60), a in (0..16i32, 0..16i32), b in (0..16i32, 0..16i32)) {
+            let mut map = Map::empty(16, 16);
+
+            for (x, y) in walls {
+                *map.get_mut(&Vector::new(x, y)).unwrap() = Tile::wall();
+            }
+
+            let (a, b) = (Vector::new(a.0, a.1), Vector::new(b.0, b.1));
+            prop_assume!(map.get(&a).unwrap().walkable() && map.get(&b).unwrap().walkable());
+
+            let from_a = field_of_view(&map, a, 6.5);
+            let from_b = field_of_view(&map, b, 6.5);
+
+            prop_assert_eq!(from_a.contains(&b), from_b.contains(&a));
+            prop_assert!(from_a.iter().all(|position| Vector::distance(&a, position) <= 6.5));
+        }
+    }
+
+    /// The previous implementation, a ray is cast to every tile in the view square, kept to compare against
+    fn raycast_field_of_view(map: &Map, origin: Vector, radius: f32) -> HashSet<Vector> {
+        let mut visible = HashSet::new();
+
+        let (top, bottom, left, right) = (
+            (origin.y as f32 - radius).floor() as i32,
+            (origin.y as f32 + radius).ceil() as i32,
+            (origin.x as f32 - radius).floor() as i32,
+            (origin.x as f32 + radius).ceil() as i32,
+        );
+
+        for x in left..=right {
+            for y in top..=bottom {
+                let position = Vector::new(x, y);
+
+                if Vector::distance(&origin, &position) <= radius && map.in_bounds(&position) {
+                    let result = map.raycast(origin, position, RaycastMode::Visibility);
+
+                    if result.hit_position().is_none_or(|hit| hit == position) {
+                        visible.insert(position);
+                    }
+                }
+            }
+        }
+
+        visible
+    }
+
+    fn cave() -> (Map, Vector) {
+        let generated = GeneratorKind::Cellular.build(100, 100, Connectivity::Join, &mut RandomNumberGenerator::seeded(1));
+
+        (generated.map, generated.layout.spawn)
+    }
+
+    #[bench]
+    fn bench_shadowcasting_open(b: &mut Bencher) {
+        let map = Map::empty(100, 100);
+        b.iter(|| field_of_view(&map, Vector::new(50, 50), VIEW_DISTANCE));
+    }
+
+    #[bench]
+    fn bench_raycasting_open(b: &mut Bencher) {
+        let map = Map::empty(100, 100);
+        b.iter(|| raycast_field_of_view(&map, Vector::new(50, 50), VIEW_DISTANCE));
+    }
+
+    #[bench]
+    fn bench_shadowcasting_cave(b: &mut Bencher) {
+        let (map, origin) = cave();
+        b.iter(|| field_of_view(&map, origin, VIEW_DISTANCE));
+    }
+
+    #[bench]
+    fn bench_raycasting_cave(b: &mut Bencher) {
+        let (map, origin) = cave();
+        b.iter(|| raycast_field_of_view(&map, origin, VIEW_DISTANCE));
+    }
+}
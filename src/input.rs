@@ -1,13 +1,37 @@
 use rltk::Rltk;
 
+use crate::{map::Stairs, vectors::{Vector, UP_VECTOR, DOWN_VECTOR, LEFT_VECTOR, RIGHT_VECTOR}};
+
 pub enum Input {
     Up,
     Down,
     Left,
     Right,
+    Ascend,
+    Descend,
     Escape,
 }
 
+impl Input {
+    pub fn delta(&self) -> Option<Vector> {
+        match self {
+            Input::Up => Some(UP_VECTOR),
+            Input::Down => Some(DOWN_VECTOR),
+            Input::Left => Some(LEFT_VECTOR),
+            Input::Right => Some(RIGHT_VECTOR),
+            Input::Ascend | Input::Descend | Input::Escape => None,
+        }
+    }
+
+    pub fn stairs(&self) -> Option<Stairs> {
+        match self {
+            Input::Ascend => Some(Stairs::Up),
+            Input::Descend => Some(Stairs::Down),
+            _ => None,
+        }
+    }
+}
+
 pub fn parse_input(ctx: &Rltk) -> Option<Input> {
     match ctx.key {
         None => None,
@@ -16,6 +40,8 @@ pub fn parse_input(ctx: &Rltk) -> Option<Input> {
             rltk::VirtualKeyCode::A | rltk::VirtualKeyCode::Left => Some(Input::Left),
             rltk::VirtualKeyCode::S | rltk::VirtualKeyCode::Down => Some(Input::Down),
             rltk::VirtualKeyCode::D | rltk::VirtualKeyCode::Right => Some(Input::Right),
+            rltk::VirtualKeyCode::Comma => Some(Input::Ascend),
+            rltk::VirtualKeyCode::Period => Some(Input::Descend),
             _ => None
         }
     }
@@ -1,24 +1,29 @@
-use std::{collections::HashMap, cmp::Ordering};
+use std::{collections::HashMap, cmp::Ordering, hash::Hash};
 
-use rand::{prelude::SliceRandom, thread_rng};
+use crate::{vectors::{Vector, Metric, Point}, ecs::{world::World, entity::EntityId}, query, components::Position};
 
-use crate::{vectors::Vector, ecs::{world::World, entity::EntityId}, query, components::Position};
+pub type Entities = Vec<EntityId>;
+pub type EntityTree = Node<Vector, Entities>;
 
-type Entities = Vec<EntityId>;
-type Entry = (Vector, Entities);
+type Entry<P, T> = (P, T);
 
-pub struct Node {
-    point: Vector,
-    entities: Entities,
-    left: Option<Box<Node>>,
-    right: Option<Box<Node>>,
+pub struct Node<P: Point, T> {
+    point: P,
+    value: T,
+    left: Option<Box<Node<P, T>>>,
+    right: Option<Box<Node<P, T>>>,
     depth: u16,
 }
 
-impl Node {
-    pub fn get(&self, point: Vector) -> Option<&Entities> {
+impl <P: Point, T> Node<P, T> {
+    /// Bulk loads a tree, the points are expected to be distinct
+    pub fn build<I: IntoIterator<Item = (P, T)>>(entries: I) -> Option<Box<Self>> {
+        _kdtree(entries.into_iter().collect(), 0)
+    }
+
+    pub fn get(&self, point: P) -> Option<&T> {
         if point == self.point {
-            Some(&self.entities)
+            Some(&self.value)
         } else {
             match self.get_side(point) {
                 Ordering::Less => self.left.as_ref()?.get(point),
@@ -27,27 +32,154 @@ impl Node {
         }
     }
 
-    pub fn iter(&self) -> KDNodeIterator {
+    pub fn iter(&self) -> KDNodeIterator<'_, P, T> {
         KDNodeIterator::new(self)
     }
 
-    fn get_side(&self, point: Vector) -> Ordering {
-        get_component(&point, self.depth).cmp(&get_component(&self.point, self.depth))
+    /// The k points closest to `point` under the metric, closest first
+    pub fn nearest(&self, point: P, k: usize, metric: Metric) -> Vec<(P, &T)> {
+        let mut found: Vec<(f32, &Node<P, T>)> = Vec::with_capacity(k + 1);
+
+        if k > 0 {
+            self._nearest(point, k, metric, &mut found);
+        }
+
+        found.into_iter().map(|(_, node)| (node.point, &node.value)).collect()
+    }
+
+    fn _nearest<'n>(&'n self, point: P, k: usize, metric: Metric, found: &mut Vec<(f32, &'n Node<P, T>)>) {
+        let distance = metric.distance(&point, &self.point);
+
+        if found.len() < k || distance < found[found.len() - 1].0 {
+            let index = found.partition_point(|(other, _)| *other <= distance);
+            found.insert(index, (distance, self));
+            found.truncate(k);
+        }
+
+        let (near, far) = match self.get_side(point) {
+            Ordering::Less => (&self.left, &self.right),
+            Ordering::Equal | Ordering::Greater => (&self.right, &self.left),
+        };
+
+        if let Some(near) = near {
+            near._nearest(point, k, metric, found);
+        }
+
+        // The distance along the splitting axis is a lower bound on the distance to anything on the far side
+        let axis = self.axis();
+        let axis_distance = (point.component(axis) - self.point.component(axis)).abs() as f32;
+
+        if let Some(far) = far {
+            if found.len() < k || axis_distance < found[found.len() - 1].0 {
+                far._nearest(point, k, metric, found);
+            }
+        }
+    }
+
+    pub fn within_radius(&self, point: P, radius: f32, metric: Metric) -> Vec<(P, &T)> {
+        let mut found = Vec::new();
+
+        self._within_radius(point, radius, metric, &mut found);
+
+        found
+    }
+
+    fn _within_radius<'n>(&'n self, point: P, radius: f32, metric: Metric, found: &mut Vec<(P, &'n T)>) {
+        if metric.distance(&point, &self.point) <= radius {
+            found.push((self.point, &self.value));
+        }
+
+        let axis = self.axis();
+        let (component, split) = (point.component(axis) as f32, self.point.component(axis) as f32);
+
+        if let Some(left) = &self.left {
+            if component - radius <= split {
+                left._within_radius(point, radius, metric, found);
+            }
+        }
+
+        if let Some(right) = &self.right {
+            if component + radius >= split {
+                right._within_radius(point, radius, metric, found);
+            }
+        }
+    }
+
+    /// Every point with min <= point <= max on every axis
+    pub fn within_rect(&self, min: P, max: P) -> Vec<(P, &T)> {
+        let mut found = Vec::new();
+
+        self._within_rect(min, max, &mut found);
+
+        found
+    }
+
+    fn _within_rect<'n>(&'n self, min: P, max: P, found: &mut Vec<(P, &'n T)>) {
+        if (0..P::DIMENSIONS).all(|axis| min.component(axis) <= self.point.component(axis) && self.point.component(axis) <= max.component(axis)) {
+            found.push((self.point, &self.value));
+        }
+
+        let axis = self.axis();
+        let split = self.point.component(axis);
+
+        if let Some(left) = &self.left {
+            if min.component(axis) <= split {
+                left._within_rect(min, max, found);
+            }
+        }
+
+        if let Some(right) = &self.right {
+            if max.component(axis) >= split {
+                right._within_rect(min, max, found);
+            }
+        }
+    }
+
+    /// The number of levels in the tree, construction guarantees at most floor(log2(n)) + 1
+    pub fn height(&self) -> usize {
+        1 + self.left.as_ref().map_or(0, |left| left.height()).max(self.right.as_ref().map_or(0, |right| right.height()))
+    }
+
+    pub fn len(&self) -> usize {
+        1 + self.left.as_ref().map_or(0, |left| left.len()) + self.right.as_ref().map_or(0, |right| right.len())
+    }
+
+    #[inline]
+    fn axis(&self) -> usize {
+        self.depth as usize % P::DIMENSIONS
+    }
+
+    /// Ties on the splitting axis are broken by the following axes, so only the node's own point compares equal
+    fn get_side(&self, point: P) -> Ordering {
+        compare(&point, &self.point, self.depth)
+    }
+}
+
+impl <P: Point + Hash + Eq, T> Node<P, Vec<T>> {
+    /// Bulk loads a tree, values sharing a point are grouped together
+    pub fn build_grouped<I: IntoIterator<Item = (P, T)>>(entries: I) -> Option<Box<Self>> {
+        let mut grouped: HashMap<P, Vec<T>> = HashMap::new();
+
+        for (point, value) in entries {
+            grouped.entry(point).or_default().push(value);
+        }
+
+        Node::build(grouped)
     }
 }
 
-pub struct KDNodeIterator<'n> {
-    stack: Vec<&'n Node>
+pub struct KDNodeIterator<'n, P: Point, T> {
+    stack: Vec<&'n Node<P, T>>
 }
 
-impl <'n> KDNodeIterator<'n> {
-    fn new(root: &'n Node) -> Self {
+impl <'n, P: Point, T> KDNodeIterator<'n, P, T> {
+    fn new(root: &'n Node<P, T>) -> Self {
         KDNodeIterator { stack: vec![root] }
     }
 }
 
-impl <'n> Iterator for KDNodeIterator<'n> {
-    type Item = (Vector, &'n Entities);
+impl <'n, P: Point, T> Iterator for KDNodeIterator<'n, P, T> {
+    type Item = (P, &'n T);
 
     fn next(&mut self) -> Option<Self::Item> {
         let node = self.stack.pop()?;
@@ -55,51 +187,165 @@ impl <'n> Iterator for KDNodeIterator<'n> {
         if let Some(left) = &node.left { self.stack.push(left) };
         if let Some(right) = &node.right { self.stack.push(right) };
 
-        Some((node.point, &node.entities))
+        Some((node.point, &node.value))
     }
 }
 
-pub fn kdtree(world: &World) -> Option<Box<Node>> {
-    let mut entities: HashMap<Vector, Entities> = HashMap::new();
+/// Builds a tree of every positioned entity in the world, keyed by tile
+pub fn kdtree(world: &World) -> Option<Box<EntityTree>> {
+    let query = query!(Position);
 
-    for entity in world.query_entities(&query!(Position)) {
-        if let Some(position) = entity.get_component::<Position>() {
-            let entry = entities.entry(position.coords());
+    let entities = world.query_entities(&query).filter_map(|entity| {
+        Some((entity.get_component::<Position>()?.coords(), entity.id()?.clone()))
+    });
 
-            if let Some(id) = entity.id() {
-                entry.or_insert(Vec::new()).push(id.clone());
-            }
-        }
-    }
+    Node::build_grouped(entities)
+}
 
-    let data: Vec<Entry> = entities.into_iter().collect();
+/// Splits on the true median of the axis, found with `select_nth_unstable` so each level costs O(n) and the whole build O(n log n)
+fn _kdtree<P: Point, T>(mut data: Vec<Entry<P, T>>, depth: u16) -> Option<Box<Node<P, T>>> {
+    if data.is_empty() {
+        return None;
+    }
 
-    _kdtree(data, 0)
-}
+    let middle = data.len() / 2;
 
-fn _kdtree(data: Vec<Entry>, depth: u16) -> Option<Box<Node>> {
-    let pivot = median(&data, depth)?;
+    data.select_nth_unstable_by(middle, |(a, _), (b, _)| { compare(a, b, depth) });
 
-    let pivot_value = get_component(&pivot.0, depth);
+    let right = data.split_off(middle + 1);
+    let (point, value) = data.pop()?;
 
     Some(Box::new(Node {
-        point: pivot.0,
-        entities: pivot.1,
-        left: _kdtree(data.clone().into_iter().filter(|(pos, _)| { get_component(pos, depth) < pivot_value }).collect(), depth + 1),
-        right: _kdtree(data.clone().into_iter().filter(|(pos, _)| { get_component(pos, depth) >= pivot_value }).collect(), depth + 1),
+        point,
+        value,
+        left: _kdtree(data, depth + 1),
+        right: _kdtree(right, depth + 1),
         depth
     }))
 }
 
-fn median(data: &Vec<Entry>, depth: u16) -> Option<Entry> {
-    let mut sample: Vec<Entry> = data.choose_multiple(&mut thread_rng(), 5).cloned().collect();
+/// Compares the points on the depth's axis, falling back to the following axes in turn
+#[inline]
+fn compare<P: Point>(a: &P, b: &P, depth: u16) -> Ordering {
+    (0..P::DIMENSIONS)
+        .map(|offset| (depth as usize + offset) % P::DIMENSIONS)
+        .map(|axis| a.component(axis).cmp(&b.component(axis)))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
 
-    sample.sort_by_cached_key(|(pos, _)| { get_component(pos, depth) });
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
 
-    Some(sample.get(sample.len() / 2)?.clone())
-}
+    use crate::{ecs::{archetype::Archetype, entity::EntityId}, vectors::{Vector, Metric}};
 
-#[inline]
-fn get_component(v: &Vector, depth: u16) -> i32 {
-    if depth & 1 == 0 { v.x } else { v.y }
+    use super::{Node, Entities};
+
+    fn entries(points: &[(i32, i32)]) -> Vec<(Vector, Entities)> {
+        let mut unique: Vec<Vector> = points.iter().map(|point| Vector::from(*point)).collect();
+        unique.sort_by_key(|point| point.tuple());
+        unique.dedup();
+
+        unique.into_iter().enumerate().map(|(index, point)| (point, vec![EntityId::new(Archetype::new(), index)])).collect()
+    }
+
+    fn sorted(mut points: Vec<Vector>) -> Vec<(i32, i32)> {
+        points.sort_by_key(|point| point.tuple());
+        points.into_iter().map(|point| point.tuple()).collect()
+    }
+
+    fn metric() -> impl Strategy<Value = Metric> {
+        prop_oneof![Just(Metric::Euclidean), Just(Metric::Chebyshev), Just(Metric::Manhattan)]
+    }
+
+    fn points() -> impl Strategy<Value = Vec<(i32, i32)>> {
+        prop::collection::vec((-30..30, -30..30), 1..80)
+    }
+
+    proptest! {
+        #[test]
+        fn test_balanced_without_duplicates(points in points()) {
+            let data = entries(&points);
+            let tree = Node::build(data.clone()).unwrap();
+
+            prop_assert_eq!(tree.len(), data.len());
+            prop_assert!(tree.height() <= (data.len() as f32).log2().floor() as usize + 1);
+
+            for (point, entities) in data.iter() {
+                prop_assert_eq!(tree.get(*point), Some(entities));
+            }
+
+            prop_assert!(tree.get(Vector::new(100, 100)).is_none());
+        }
+
+        #[test]
+        fn test_construction_deterministic(points in points()) {
+            let data = entries(&points);
+            let mut reversed = data.clone();
+            reversed.reverse();
+
+            let a: Vec<Vector> = Node::build(data).unwrap().iter().map(|(point, _)| point).collect();
+            let b: Vec<Vector> = Node::build(reversed).unwrap().iter().map(|(point, _)| point).collect();
+
+            prop_assert_eq!(a, b);
+        }
+
+        #[test]
+        fn test_nearest_matches_brute_force(points in points(), x in -35..35, y in -35..35, k in 0usize..10, metric in metric()) {
+            let data = entries(&points);
+            let tree = Node::build(data.clone()).unwrap();
+            let target = Vector::new(x, y);
+
+            let found: Vec<f32> = tree.nearest(target, k, metric).into_iter().map(|(point, _)| metric.distance(&target, &point)).collect();
+
+            let mut expected: Vec<f32> = data.iter().map(|(point, _)| metric.distance(&target, point)).collect();
+            expected.sort_by(|a, b| a.total_cmp(b));
+            expected.truncate(k);
+
+            prop_assert_eq!(found, expected);
+        }
+
+        #[test]
+        fn test_within_radius_matches_brute_force(points in points(), x in -35..35, y in -35..35, radius in 0.0f32..20.0, metric in metric()) {
+            let data = entries(&points);
+            let tree = Node::build(data.clone()).unwrap();
+            let target = Vector::new(x, y);
+
+            let found = tree.within_radius(target, radius, metric).into_iter().map(|(point, _)| point).collect();
+            let expected = data.iter().map(|(point, _)| *point).filter(|point| metric.distance(&target, point) <= radius).collect();
+
+            prop_assert_eq!(sorted(found), sorted(expected));
+        }
+
+        #[test]
+        fn test_within_rect_matches_brute_force(points in points(), min in (-35..35, -35..35), size in (0..30, 0..30)) {
+            let data = entries(&points);
+            let tree = Node::build(data.clone()).unwrap();
+            let (min, max) = (Vector::from(min), Vector::new(min.0 + size.0, min.1 + size.1));
+
+            let found = tree.within_rect(min, max).into_iter().map(|(point, _)| point).collect();
+            let expected = data.iter().map(|(point, _)| *point).filter(|point| min.x <= point.x && point.x <= max.x && min.y <= point.y && point.y <= max.y).collect();
+
+            prop_assert_eq!(sorted(found), sorted(expected));
+        }
+
+        #[test]
+        fn test_three_dimensional_payloads(points in prop::collection::vec((-10..10, -10..10, -10..10), 1..60), target in (-12..12, -12..12, -12..12), k in 1usize..6) {
+            let tree = Node::build_grouped(points.iter().enumerate().map(|(index, (x, y, z))| ([*x, *y, *z], index))).unwrap();
+            let target = [target.0, target.1, target.2];
+
+            for (index, (x, y, z)) in points.iter().enumerate() {
+                prop_assert!(tree.get([*x, *y, *z]).unwrap().contains(&index));
+            }
+
+            let found: Vec<f32> = tree.nearest(target, k, Metric::Euclidean).into_iter().map(|(point, _)| Metric::Euclidean.distance(&target, &point)).collect();
+
+            let mut expected: Vec<f32> = tree.iter().map(|(point, _)| Metric::Euclidean.distance(&target, &point)).collect();
+            expected.sort_by(|a, b| a.total_cmp(b));
+            expected.truncate(k);
+
+            prop_assert_eq!(found, expected);
+        }
+    }
 }
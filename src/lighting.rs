@@ -0,0 +1,140 @@
+use rltk::RGB;
+
+use crate::{components::LightSource, constants::MIN_VISIBLE_LIGHT, fov, map::Map, vectors::Vector};
+
+/// How much of its colour an unlit tile keeps when tinted, so dark tiles stay readable
+const DARKNESS_TINT: f32 = 0.25;
+
+/// The light level and colour of every tile, lit by the ambient light and every `LightSource`
+#[derive(Clone, PartialEq, Debug)]
+pub struct LightMap {
+    width: usize,
+    height: usize,
+    ambient: u8,
+    levels: Vec<u8>,
+    colors: Vec<(f32, f32, f32)>,
+}
+
+impl LightMap {
+    /// A light map lit only by the ambient light, which is white
+    pub fn new(width: usize, height: usize, ambient: u8) -> Self {
+        let white = ambient as f32 / 255.0;
+
+        LightMap {
+            width,
+            height,
+            ambient,
+            levels: vec![ambient; width * height],
+            colors: vec![(white, white, white); width * height],
+        }
+    }
+
+    /// A light map of the map's size lit by the ambient light and the sources at their positions
+    pub fn compute<'s>(map: &Map, ambient: u8, sources: impl IntoIterator<Item = (Vector, &'s LightSource)>) -> Self {
+        let mut lights = LightMap::new(map.width, map.height, ambient);
+
+        for (position, source) in sources {
+            lights.add(map, position, source);
+        }
+
+        lights
+    }
+
+    /// Adds the light of a source at the origin, it fades linearly with distance and through partially opaque tiles
+    pub fn add(&mut self, map: &Map, origin: Vector, source: &LightSource) {
+        for (position, arriving) in fov::light_field(map, origin, source.radius, source.intensity) {
+            let Some(index) = self.index(&position) else {
+                continue;
+            };
+
+            let falloff = 1.0 - Vector::distance(&origin, &position) / (source.radius + 1.0);
+            let amount = arriving as f32 * falloff;
+
+            self.levels[index] = (self.levels[index] as f32 + amount).min(255.0) as u8;
+
+            let (r, g, b) = &mut self.colors[index];
+            *r = (*r + source.color.0 as f32 / 255.0 * amount / 255.0).min(1.0);
+            *g = (*g + source.color.1 as f32 / 255.0 * amount / 255.0).min(1.0);
+            *b = (*b + source.color.2 as f32 / 255.0 * amount / 255.0).min(1.0);
+        }
+    }
+
+    pub fn ambient(&self) -> u8 {
+        self.ambient
+    }
+
+    /// The light level of the tile, tiles outside the map are dark
+    pub fn level(&self, position: &Vector) -> u8 {
+        self.index(position).map_or(0, |index| self.levels[index])
+    }
+
+    /// Whether the tile is lit well enough to be seen
+    pub fn lit(&self, position: &Vector) -> bool {
+        self.level(position) >= MIN_VISIBLE_LIGHT
+    }
+
+    /// The colour of the light on the tile, every channel lies between 0 and 1 and is already darkened by the light level
+    pub fn color(&self, position: &Vector) -> (f32, f32, f32) {
+        self.index(position).map_or((0.0, 0.0, 0.0), |index| self.colors[index])
+    }
+
+    /// The colour tinted by the light falling on the tile
+    pub fn tint(&self, position: &Vector, color: (u8, u8, u8)) -> RGB {
+        let (r, g, b) = self.color(position);
+        let tint = |channel: u8, light: f32| channel as f32 / 255.0 * (DARKNESS_TINT + (1.0 - DARKNESS_TINT) * light);
+
+        RGB::from_f32(tint(color.0, r), tint(color.1, g), tint(color.2, b))
+    }
+
+    fn index(&self, position: &Vector) -> Option<usize> {
+        if position.x < 0 || position.y < 0 || position.x >= self.width as i32 || position.y >= self.height as i32 {
+            return None;
+        }
+
+        Some(position.y as usize * self.width + position.x as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{components::{LightSource, Viewshed}, map::{Map, Tile}, vectors::Vector};
+
+    use super::LightMap;
+
+    #[test]
+    fn test_light_fades_and_is_blocked() {
+        let mut map = Map::empty(20, 5);
+        *map.get_mut(&Vector::new(6, 2)).unwrap() = Tile::wall();
+
+        let torch = LightSource::new(4.5, (255, 0, 0), 255);
+        let lights = LightMap::compute(&map, 0, [(Vector::new(2, 2), &torch)]);
+
+        assert_eq!(lights.level(&Vector::new(2, 2)), 255);
+        assert!(lights.level(&Vector::new(3, 2)) > lights.level(&Vector::new(5, 2)));
+        assert!(lights.lit(&Vector::new(6, 2)));
+        assert_eq!(lights.level(&Vector::new(7, 2)), 0);
+        assert_eq!(lights.level(&Vector::new(-1, 2)), 0);
+
+        let (r, g, b) = lights.color(&Vector::new(3, 2));
+        assert!(r > 0.0 && g == 0.0 && b == 0.0);
+        assert_eq!(lights.tint(&Vector::new(15, 2), (255, 255, 255)), rltk::RGB::from_f32(0.25, 0.25, 0.25));
+
+        assert_eq!(LightMap::compute(&map, 40, []).level(&Vector::new(15, 2)), 40);
+    }
+
+    #[test]
+    fn test_darkness_hides_tiles() {
+        let mut map = Map::empty(20, 5);
+
+        let torch = LightSource::new(3.5, (255, 255, 255), 255);
+        let lights = LightMap::compute(&map, 0, [(Vector::new(12, 2), &torch)]);
+
+        let mut viewshed = Viewshed::new(15.0);
+        viewshed.update(&mut map, Vector::new(2, 2), Some(&lights), false, None);
+
+        assert!(viewshed.contains(&Vector::new(2, 2)));
+        assert!(!viewshed.contains(&Vector::new(3, 2)));
+        assert!(viewshed.contains(&Vector::new(10, 2)));
+        assert!(!viewshed.contains(&Vector::new(17, 2)));
+    }
+}
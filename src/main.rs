@@ -1,65 +1,54 @@
 #![allow(dead_code)]
 #![feature(downcast_unchecked)]
+#![cfg_attr(test, feature(test))]
+
+#[cfg(test)]
+extern crate test;
 
 use std::process::exit;
-use ecs::{entity::{EntityId, Entity}, world::World};
+use ecs::world::World;
+use scene::{Scene, SceneStack};
+use setup::GameSetup;
 use include_dir::{include_dir, Dir};
 use rltk::{Rltk, GameState};
 use ui::{UiAction, UiPanel, UiMaster};
 use theme::Theme;
 
+mod dungeon;
 mod map;
+mod mapgen;
+mod pathfinding;
 mod components;
 mod ecs;
 mod macros;
 mod systems;
 mod entities;
+mod fov;
 mod constants;
 mod vectors;
 mod transform;
 mod theme;
+mod tiles;
 mod input;
 mod ui;
 mod kdtree;
+mod lighting;
+mod scene;
+mod setup;
+mod spatial;
 
 static RAWS: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/raws");
 
-pub struct KeyEntities {
-    player: Option<EntityId>
-}
-
-impl KeyEntities {
-    pub fn new() -> Self {
-        KeyEntities { player: None }
-    }
-
-    pub fn set_player(&mut self, player: &EntityId) {
-        match self.player {
-            Some(_) => panic!("Already had a player entity!"),
-            None => { self.player = Some(player.clone()); }
-        }
-    }
-
-    pub fn player<'a>(&'a self, world: &'a World) -> Option<&Entity> {
-        world.get(self.player.as_ref()?)
-    }
-}
-
-impl Default for KeyEntities {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 pub struct State {
-    world: World,
+    scenes: SceneStack,
     ui_panels: Vec<UiPanel>,
     ui_master: UiMaster,
+    setup: GameSetup,
 }
 
 impl State {
-    fn new(ui: UiMaster) -> Self {
-        let mut state = State { world: World::new(), ui_panels: Vec::new(), ui_master: ui.clone() };
+    fn new(ui: UiMaster, scenes: SceneStack, setup: GameSetup) -> Self {
+        let mut state = State { scenes, ui_panels: Vec::new(), ui_master: ui.clone(), setup };
 
         for panel in ui.open_panels() {
             state.open(panel);
@@ -68,6 +57,21 @@ impl State {
         state
     }
 
+    pub fn world(&self) -> &World {
+        self.scenes.world()
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        self.scenes.world_mut()
+    }
+
+    /// Drops every scene above the root and rebuilds the root world from the game setup
+    pub fn new_game(&mut self) {
+        self.scenes.clear();
+
+        self.setup.build(self.scenes.world_mut()).expect("a valid game setup");
+    }
+
     pub fn open(&mut self, panel: &UiPanel) {
         self.ui_panels.push(panel.clone());
     }
@@ -102,7 +106,7 @@ impl State {
         }
 
         if let Some(starting_index) = starting_index {
-            if let Some(theme) = self.world.get_resource::<Theme>() {
+            if let Some(theme) = self.scenes.world().get_resource::<Theme>() {
                 ctx.cls_bg(theme.background_color);
             } else {
                 ctx.cls();
@@ -110,16 +114,16 @@ impl State {
 
             for i in starting_index..self.ui_panels.len() {
                 if let Some(panel) = self.ui_panels.get(i) {
-                    panel.render(&self.world, ctx)
+                    panel.render(self.scenes.world(), ctx)
                 }
             }
         } else {
             for panel in self.ui_panels.iter() {
-                panel.render(&self.world, ctx);
+                panel.render(self.scenes.world(), ctx);
             }
         }
 
-        self.ui_panels.last_mut()?.tick(&self.world, ctx)
+        self.ui_panels.last_mut()?.tick(self.scenes.world(), ctx)
     }
 }
 
@@ -140,11 +144,21 @@ impl GameState for State {
 
                     self.open_by_ids(&ids);
                 }
+                UiAction::NewGame { ids } => {
+                    let ids = ids.clone();
+
+                    self.new_game();
+                    self.close_all();
+
+                    self.open_by_ids(&ids);
+                },
                 UiAction::Exit => { self.close(); },
             },
             None => ()
         }
 
+        self.world_mut().apply_deferred();
+
         if self.ui_panels.is_empty() {
             exit(0); 
         }
@@ -163,27 +177,12 @@ fn main() -> rltk::BError {
 
     ui_master.verify();
 
-    let mut gs = State::new(ui_master);
-
-    let mut key_entities = KeyEntities::new();
-
-    let _ = gs.world.insert_resource(Theme::new()).unwrap();
-    let _ = gs.world.insert_resource(map::Map::new(constants::MAP_SIZE.0, constants::MAP_SIZE.1)).unwrap();
-    let _ = gs.world.insert_resource(systems::TickInfo::new()).unwrap();
-
-    add_system!(gs.world, systems::TickSystem::new(), 1000);
-    add_system!(gs.world, systems::ViewSystem::new(), -900);
-    add_system!(gs.world, systems::DebugSystem::new(components::DebugLevel::None), -1000);
+    let setup: GameSetup = serde_json::from_str(RAWS.get_file("setup.json").unwrap().contents_utf8().unwrap()).unwrap();
 
-    let player = gs.world.insert(entities::player(
-        Ok(Entity::new()),
-        "Hazel".into(),
-        (constants::MAP_SIZE.0 / 2) as i32,
-        (constants::MAP_SIZE.1 / 2) as i32
-    ).unwrap().build()).unwrap();
-    key_entities.set_player(&player);
+    let mut world = World::new();
+    setup.build(&mut world).unwrap();
 
-    let _ = gs.world.insert_resource(key_entities).unwrap();
+    let gs = State::new(ui_master, SceneStack::new(Scene::new(constants::DUNGEON_SCENE.into(), world)), setup);
 
     rltk::main_loop(context, gs)
 }
@@ -1,45 +1,89 @@
-use std::fmt::Display;
+use std::{collections::HashMap, fmt::Display};
 
-use rltk::Rltk;
+use rltk::{Rltk, RGB};
+use serde::Deserialize;
 
-use crate::{clamp, constants::MAP_SIZE, vectors::Vector, ecs::world::World, theme::Theme, transform::Transform, systems::TickInfo};
+use crate::{clamp, tiles::{self, tile_types, TileType, TileTypeId}, mapgen::{Connectivity, GeneratorKind}, vectors::{Vector, UP_VECTOR, DOWN_VECTOR, LEFT_VECTOR, RIGHT_VECTOR}, ecs::{world::World, entity::EntityId}, theme::Theme, transform::Transform, systems::TickInfo, components::{BlocksTile, Position}, lighting::LightMap, query};
 
-/// Represents a tile
+/// Represents a tile, its glyph, colours and flags come from its `TileType`
 /// Strength:
 ///     0:       The tile is walkable
 ///     1 - 255: The tile is not broken and therefore is not walkable
 /// Opaqueness:
-/// This value is subtracted from the light value to determine if a tile is visible
+/// This value is subtracted from the light passing through, both from light sources and from a viewer's line of sight
 ///     0:       The tile is copletely visible and lets through all lights
 ///     1 - 255: The tile blocks out some light but will be visible if hit with a ray
 #[derive(PartialEq, Eq, Copy, Clone)]
 pub struct Tile {
+    kind: TileTypeId,
     strength: u8,
     opaqueness: u8,
     discovered: bool,
     last_seen: Option<usize>,
 }
 
+/// The direction a staircase leads in
+#[derive(Deserialize, PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Stairs {
+    Up,
+    Down,
+}
+
 impl Tile {
-    pub fn new(strength: u8, opaqueness: u8) -> Self {
+    pub fn new(kind: TileTypeId) -> Self {
+        let tile_type = tile_types().get(kind);
+
         Tile {
-            strength,
-            opaqueness,
+            kind,
+            strength: tile_type.strength,
+            opaqueness: tile_type.opaqueness,
             discovered: false,
             last_seen: None,
         }
     }
 
+    /// Panics if no tile type has the name
+    pub fn of(name: &str) -> Self {
+        Tile::new(tile_types().id(name).unwrap_or_else(|| panic!("unknown tile type: {}", name)))
+    }
+
     pub fn ground() -> Self {
-        Tile::new(0, 0)
+        Tile::of(tiles::GROUND)
     }
 
     pub fn wall() -> Self {
-        Tile::new(255, 255)
+        Tile::of(tiles::WALL)
     }
 
     pub fn window() -> Self {
-        Tile::new(255, 0)
+        Tile::of(tiles::WINDOW)
+    }
+
+    pub fn stairs(direction: Stairs) -> Self {
+        match direction {
+            Stairs::Up => Tile::of(tiles::STAIRS_UP),
+            Stairs::Down => Tile::of(tiles::STAIRS_DOWN),
+        }
+    }
+
+    pub fn kind(&self) -> TileTypeId {
+        self.kind
+    }
+
+    pub fn tile_type(&self) -> &'static TileType {
+        tile_types().get(self.kind)
+    }
+
+    pub fn get_stairs(&self) -> Option<Stairs> {
+        self.tile_type().stairs()
+    }
+
+    pub fn opaqueness(&self) -> u8 {
+        self.opaqueness
+    }
+
+    pub fn movement_cost(&self) -> f32 {
+        self.tile_type().movement_cost
     }
 
     pub fn set_both(&mut self, value: u8) {
@@ -51,6 +95,11 @@ impl Tile {
         self.strength == 0
     }
 
+    /// Walkable or a door, which can be opened to walk through
+    pub fn passable(&self) -> bool {
+        self.walkable() || self.tile_type().door()
+    }
+
     pub fn discover(&mut self) {
         self.discovered = true;
     }
@@ -76,7 +125,7 @@ impl Tile {
 
 impl Display for Tile {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Tile(s={},o={})", self.strength, self.opaqueness)
+        write!(f, "Tile({},s={},o={})", self.tile_type().name, self.strength, self.opaqueness)
     }
 }
 
@@ -116,58 +165,47 @@ impl <'t> RaycastResult<'t> {
     }
 }
 
+/// The outcome of damaging a tile
+/// OutOfBounds:   There is no tile at the position
+/// AlreadyBroken: The tile's strength already was 0, nothing changed
+/// Damaged:       The tile lost strength but still stands
+/// Broken:        The tile's strength reached 0 and it turned into the type it breaks into
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TileDamageResult {
+    OutOfBounds,
+    AlreadyBroken,
+    Damaged { remaining: u8 },
+    Broken { from: TileTypeId, into: TileTypeId },
+}
+
+/// A tile at the position was replaced by one of a different type
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TerrainChange {
+    pub position: Vector,
+    pub from: TileTypeId,
+    pub into: TileTypeId,
+}
+
 pub struct Map {
     pub width: usize,
     pub height: usize,
     data: Vec<Tile>,
+    blockers: Vec<Option<EntityId>>,
+    blocker_index: HashMap<EntityId, usize>,
+    changes: Vec<TerrainChange>,
+    links: HashMap<Vector, Vec<Vector>>,
+    memory: Vec<Option<rltk::FontCharType>>,
 }
 
 impl Map {
+    /// A map built by the default generator, every walkable tile is reachable from every other
     pub fn new(width: usize, height: usize) -> Self {
-        let mut map = Map::empty(width, height);
-
-        for x in 0..(width as i32) {
-            if let Some(tile) = map.get_mut(&Vector::from((x, 0))) {
-                tile.set_both(255);
-            }
-
-            if let Some(tile) = map.get_mut(&Vector::from((x, height as i32 - 1))) {
-                tile.set_both(255);
-            }
-        }
-
-        for y in 0..(height as i32) {
-            if let Some(tile) = map.get_mut(&Vector::from((0, y))) {
-                tile.set_both(255);
-            }
-
-            if let Some(tile) = map.get_mut(&Vector::from((width as i32 - 1, y))) {
-                tile.set_both(255);
-            }
-        }
-
-        let mut rng = rltk::RandomNumberGenerator::new();
-
-        let middle = Vector::new((MAP_SIZE.0 / 2) as i32, (MAP_SIZE.1 / 2) as i32);
-
-        for _i in 0..(MAP_SIZE.0 * MAP_SIZE.1 / 5) {
-            let x = rng.roll_dice(1, MAP_SIZE.0 as i32 - 1);
-            let y = rng.roll_dice(1, MAP_SIZE.1 as i32 - 1);
-
-            let pos = Vector::new(x, y);
-
-            if pos != middle {
-                if let Some(tile) = map.get_mut(&pos) {
-                    tile.set_both(255);
-                }
-            }
-        }
-
-        map
+        GeneratorKind::default().build(width, height, Connectivity::Join, &mut rltk::RandomNumberGenerator::new()).map
     }
 
     pub fn render(&self, world: &World, ctx: &mut Rltk, theme: &Theme, transform: Transform, position: Vector, size: Vector) {
         let tick = world.get_resource::<TickInfo>().expect("a TickInfo object in world").last_view_update_tick();
+        let lights = world.get_resource::<LightMap>();
 
         for x in position.x..(position.x + size.x) {
             for y in position.y..(position.y + size.y) {
@@ -175,18 +213,21 @@ impl Map {
 
                 if let Some(tile) = self.get(&final_position) {
                     if tile.discovered() {
-                        let glyph: rltk::FontCharType = {
-                            if tile.walkable() {
-                                rltk::to_cp437('.')
-                            } else {
-                                rltk::to_cp437('#')
-                            }
+                        let tile_type = tile.tile_type();
+
+                        let visible = tile.visible(tick);
+
+                        // Only what is seen right now is tinted by the light falling on it
+                        let terrain_color = match (visible, lights.as_ref()) {
+                            (true, Some(lights)) => lights.tint(&final_position, tile_type.visible_color),
+                            (true, None) => RGB::from(tile_type.visible_color),
+                            (false, _) => RGB::from(tile_type.remembered_color),
                         };
 
-                        let terrain_color = if tile.visible(tick) {
-                            theme.terrain_color_visible
-                        } else {
-                            theme.terrain_color_discovered
+                        // Tiles out of view show the entity last seen on them in the remembered colour
+                        let glyph = match visible {
+                            true => rltk::to_cp437(tile_type.glyph),
+                            false => self.remembered(&final_position).unwrap_or_else(|| rltk::to_cp437(tile_type.glyph)),
                         };
 
                         ctx.set(x, y, terrain_color, theme.background_color, glyph);
@@ -197,10 +238,169 @@ impl Map {
     }
 
     pub fn empty(width: usize, height: usize) -> Self {
+        Map::filled(width, height, Tile::ground())
+    }
+
+    pub fn filled(width: usize, height: usize, tile: Tile) -> Self {
         Map {
             width,
             height,
-            data: vec![Tile::ground(); width * height],
+            data: vec![tile; width * height],
+            blockers: vec![None; width * height],
+            blocker_index: HashMap::new(),
+            changes: Vec::new(),
+            links: HashMap::new(),
+            memory: vec![None; width * height],
+        }
+    }
+
+    /// Lowers the tile's strength, a tile reaching 0 is replaced by the type it breaks into which also sets its opaqueness
+    pub fn damage_tile(&mut self, position: &Vector, amount: u8) -> TileDamageResult {
+        let Some(tile) = self.get_mut(position) else {
+            return TileDamageResult::OutOfBounds;
+        };
+
+        if tile.strength == 0 {
+            return TileDamageResult::AlreadyBroken;
+        }
+
+        tile.strength = tile.strength.saturating_sub(amount);
+
+        if tile.strength > 0 {
+            return TileDamageResult::Damaged { remaining: tile.strength };
+        }
+
+        let from = tile.kind();
+        let into = tile_types().broken(from);
+
+        self.replace_tile(position, Tile::new(into));
+
+        TileDamageResult::Broken { from, into }
+    }
+
+    /// Swaps the tile for another while keeping what the player remembers of it, the change is recorded for `take_changes`
+    pub fn replace_tile(&mut self, position: &Vector, mut tile: Tile) -> bool {
+        let Some(old) = self.get_mut(position) else {
+            return false;
+        };
+
+        tile.discovered = old.discovered;
+        tile.last_seen = old.last_seen;
+
+        let from = old.kind();
+        *old = tile;
+
+        self.changes.push(TerrainChange { position: *position, from, into: tile.kind() });
+
+        true
+    }
+
+    /// Switches the tile to its toggled type along with every tile linked to it, as a lever does with its portcullis
+    /// Returns false if the tile can not be used
+    pub fn use_tile(&mut self, position: &Vector) -> bool {
+        if !self.toggle_tile(position) {
+            return false;
+        }
+
+        for linked in self.links.get(position).cloned().unwrap_or_default() {
+            self.toggle_tile(&linked);
+        }
+
+        true
+    }
+
+    /// Tiles are never toggled into an unwalkable type while an entity blocks them, so doors do not close on anyone
+    fn toggle_tile(&mut self, position: &Vector) -> bool {
+        let Some(into) = self.get(position).and_then(|tile| tile_types().toggled(tile.kind())) else {
+            return false;
+        };
+
+        let tile = Tile::new(into);
+
+        if !tile.walkable() && self.blocker(position).is_some() {
+            return false;
+        }
+
+        self.replace_tile(position, tile)
+    }
+
+    /// Using the tile at `from` also toggles the tile at `to`
+    pub fn link(&mut self, from: Vector, to: Vector) {
+        let targets = self.links.entry(from).or_default();
+
+        if !targets.contains(&to) {
+            targets.push(to);
+        }
+    }
+
+    /// Drains the terrain changes made since the last call
+    pub fn take_changes(&mut self) -> Vec<TerrainChange> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// The glyph of the entity last seen on the tile, if any
+    pub fn remembered(&self, position: &Vector) -> Option<rltk::FontCharType> {
+        if !self.in_bounds(position) {
+            return None;
+        }
+
+        unsafe { self.memory[self.coords_to_index_unchecked(position)] }
+    }
+
+    /// Remembers the glyph seen on the tile, None forgets whatever was seen there before
+    pub fn remember(&mut self, position: &Vector, glyph: Option<rltk::FontCharType>) {
+        if self.in_bounds(position) {
+            let index = unsafe { self.coords_to_index_unchecked(position) };
+
+            self.memory[index] = glyph;
+        }
+    }
+
+    /// The entity blocking the tile, if any
+    pub fn blocker(&self, position: &Vector) -> Option<&EntityId> {
+        if !self.in_bounds(position) {
+            return None;
+        }
+
+        unsafe { self.blockers[self.coords_to_index_unchecked(position)].as_ref() }
+    }
+
+    /// Places a blocking entity on a tile, moving it if it was already placed, returns false if another entity blocks the tile
+    pub fn set_blocker(&mut self, position: &Vector, id: &EntityId) -> bool {
+        if !self.in_bounds(position) {
+            return false;
+        }
+
+        let index = unsafe { self.coords_to_index_unchecked(position) };
+
+        match &self.blockers[index] {
+            Some(other) if other != id => false,
+            _ => {
+                self.clear_blocker(id);
+
+                self.blockers[index] = Some(id.clone());
+                self.blocker_index.insert(id.clone(), index);
+
+                true
+            }
+        }
+    }
+
+    pub fn clear_blocker(&mut self, id: &EntityId) {
+        if let Some(index) = self.blocker_index.remove(id) {
+            self.blockers[index] = None;
+        }
+    }
+
+    /// Rebuilds the occupancy index from every positioned entity which blocks its tile
+    pub fn index_blockers(&mut self, world: &World) {
+        self.blockers.iter_mut().for_each(|blocker| *blocker = None);
+        self.blocker_index.clear();
+
+        for entity in world.query_entities(&query!(Position, BlocksTile)) {
+            if let (Some(position), Some(id)) = (entity.get_component::<Position>(), entity.id()) {
+                self.set_blocker(&position.coords(), id);
+            }
         }
     }
 
@@ -284,6 +484,50 @@ impl Map {
         (0 <= x && x < self.width as i32) && (0 <= y && y < self.height as i32)
     }
 
+    /// Labels every walkable tile with the region it belongs to by flood filling
+    pub fn regions(&self) -> Regions {
+        let mut labels: Vec<Option<usize>> = vec![None; self.data.len()];
+        let mut sizes = Vec::new();
+        let mut stack = Vec::new();
+
+        for start in 0..self.data.len() {
+            if labels[start].is_some() || !self.data[start].passable() {
+                continue;
+            }
+
+            let region = sizes.len();
+            let mut size = 0;
+
+            labels[start] = Some(region);
+            stack.push(start);
+
+            while let Some(index) = stack.pop() {
+                size += 1;
+
+                let (x, y) = unsafe { self.index_to_coords_unchecked(index) };
+
+                for neighbour in [UP_VECTOR, DOWN_VECTOR, LEFT_VECTOR, RIGHT_VECTOR] {
+                    let position = Vector::new(x, y) + neighbour;
+
+                    if !self.in_bounds(&position) {
+                        continue;
+                    }
+
+                    let next = unsafe { self.coords_to_index_unchecked(&position) };
+
+                    if labels[next].is_none() && self.data[next].passable() {
+                        labels[next] = Some(region);
+                        stack.push(next);
+                    }
+                }
+            }
+
+            sizes.push(size);
+        }
+
+        Regions { width: self.width, labels, sizes }
+    }
+
     pub fn raycast(&self, start: Vector, end: Vector, mode: RaycastMode) -> RaycastResult {
         let mut light: u8 = 255;
         let mut last: Option<Vector> = None;
@@ -322,7 +566,182 @@ impl Map {
     }
 }
 
+/// Passable tiles grouped into regions connected by straight steps, regions are numbered in scan order
+pub struct Regions {
+    width: usize,
+    labels: Vec<Option<usize>>,
+    sizes: Vec<usize>,
+}
+
+impl Regions {
+    pub fn region(&self, position: &Vector) -> Option<usize> {
+        if position.x < 0 || position.y < 0 || position.x >= self.width as i32 {
+            return None;
+        }
+
+        *self.labels.get(position.y as usize * self.width + position.x as usize)?
+    }
+
+    pub fn size(&self, region: usize) -> usize {
+        self.sizes.get(region).copied().unwrap_or(0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// The region with the most tiles, the first one found on ties
+    pub fn largest(&self) -> Option<usize> {
+        (0..self.sizes.len()).rev().max_by_key(|region| self.sizes[*region])
+    }
+
+    pub fn connected(&self, a: &Vector, b: &Vector) -> bool {
+        match (self.region(a), self.region(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    pub fn tiles(&self, region: usize) -> impl Iterator<Item = Vector> + '_ {
+        self.labels.iter().enumerate()
+            .filter(move |(_, label)| **label == Some(region))
+            .map(|(index, _)| Vector::new((index % self.width) as i32, (index / self.width) as i32))
+    }
+}
+
 pub enum RaycastMode {
     Walkable,
     Visibility,
 }
+
+/// Keeps the map's occupancy index in sync with despawned entities and entities which changed archetype, including gaining or losing `BlocksTile`
+pub fn sync_blockers(world: &mut World, old: &EntityId, new: Option<&EntityId>) {
+    if let Some(mut map) = world.get_resource_mut::<Map>() {
+        map.clear_blocker(old);
+
+        if let Some(new) = new {
+            block_tile(&mut map, world, new);
+        }
+    }
+}
+
+/// Marks the tiles of spawned entities which block them
+pub fn index_inserted_blocker(world: &mut World, id: &EntityId) {
+    if let Some(mut map) = world.get_resource_mut::<Map>() {
+        block_tile(&mut map, world, id);
+    }
+}
+
+fn block_tile(map: &mut Map, world: &World, id: &EntityId) {
+    if !world.has_component::<BlocksTile>(id) {
+        return;
+    }
+
+    if let Some(position) = world.get_component::<Position>(id) {
+        map.set_blocker(&position.coords(), id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{components::{register_components, BlocksTile, Position}, ecs::{archetype::Archetype, entity::{Entity, EntityId}, world::World}, mapgen::Rect, vectors::Vector};
+
+    use super::{index_inserted_blocker, sync_blockers, Map, Tile};
+
+    #[test]
+    fn test_use_tiles() {
+        let mut map = Map::empty(5, 5);
+        let (door, lever, portcullis) = (Vector::new(1, 1), Vector::new(3, 1), Vector::new(3, 3));
+
+        *map.get_mut(&door).unwrap() = Tile::of("door_closed");
+        *map.get_mut(&lever).unwrap() = Tile::of("lever_off");
+        *map.get_mut(&portcullis).unwrap() = Tile::of("portcullis_closed");
+        map.link(lever, portcullis);
+
+        assert!(!map.use_tile(&Vector::new(0, 0)));
+
+        assert!(map.use_tile(&door));
+        assert!(map.get(&door).unwrap().walkable());
+        assert_eq!(map.get(&door).unwrap().tile_type().opaqueness, 0);
+
+        map.set_blocker(&door, &EntityId::new(Archetype::new(), 0));
+        assert!(!map.use_tile(&door));
+
+        assert!(map.use_tile(&lever));
+        assert_eq!(map.get(&lever).unwrap().tile_type().name, "lever_on");
+        assert!(map.get(&portcullis).unwrap().walkable());
+
+        assert_eq!(map.take_changes().len(), 3);
+        assert!(map.take_changes().is_empty());
+    }
+
+    #[test]
+    fn test_regions() {
+        let mut map = Map::filled(10, 6, Tile::wall());
+
+        for (room, size) in [(Rect::new(1, 1, 3, 3), 9), (Rect::new(6, 1, 2, 4), 8)] {
+            for position in room.points() {
+                *map.get_mut(&position).unwrap() = Tile::ground();
+            }
+
+            let regions = map.regions();
+            let region = regions.region(&room.center()).unwrap();
+
+            assert_eq!(regions.size(region), size);
+        }
+
+        let regions = map.regions();
+
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions.largest(), regions.region(&Vector::new(1, 1)));
+        assert!(regions.connected(&Vector::new(1, 1), &Vector::new(3, 3)));
+        assert!(!regions.connected(&Vector::new(1, 1), &Vector::new(6, 1)));
+        assert_eq!(regions.region(&Vector::new(0, 0)), None);
+        assert_eq!(regions.tiles(regions.region(&Vector::new(7, 4)).unwrap()).count(), 8);
+    }
+
+    #[test]
+    fn test_hooks_keep_the_blockers_in_sync() {
+        let mut world = World::new();
+        world.insert_resource(Map::empty(10, 10)).unwrap();
+        world.add_id_hook(sync_blockers).add_insert_hook(index_inserted_blocker);
+
+        let blocker = world.insert(Entity::new().insert_component(Position::new(3, 4, 0)).unwrap().insert_component(BlocksTile::new()).unwrap().build()).unwrap();
+        let passer = world.insert(Entity::new().insert_component(Position::new(5, 5, 0)).unwrap().build()).unwrap();
+
+        assert_eq!(world.get_resource::<Map>().unwrap().blocker(&Vector::new(3, 4)), Some(&blocker));
+        assert!(world.get_resource::<Map>().unwrap().blocker(&Vector::new(5, 5)).is_none());
+
+        let blocker = world.remove_component::<BlocksTile>(&blocker).unwrap();
+        let passer = world.add_component(&passer, BlocksTile::new()).unwrap();
+
+        assert!(world.get_resource::<Map>().unwrap().blocker(&Vector::new(3, 4)).is_none());
+        assert_eq!(world.get_resource::<Map>().unwrap().blocker(&Vector::new(5, 5)), Some(&passer));
+
+        let blocker = world.add_component(&blocker, BlocksTile::new()).unwrap();
+        world.despawn(&passer);
+
+        assert_eq!(world.get_resource::<Map>().unwrap().blocker(&Vector::new(3, 4)), Some(&blocker));
+        assert!(world.get_resource::<Map>().unwrap().blocker(&Vector::new(5, 5)).is_none());
+    }
+
+    #[test]
+    fn test_clones_do_not_block() {
+        let mut world = World::new();
+        register_components(&mut world);
+        world.insert_resource(Map::empty(10, 10)).unwrap();
+        world.add_id_hook(sync_blockers).add_insert_hook(index_inserted_blocker);
+
+        let blocker = world.insert(Entity::new().insert_component(Position::new(3, 4, 0)).unwrap().insert_component(BlocksTile::new()).unwrap().build()).unwrap();
+        let report = world.clone_entity(&blocker).unwrap();
+
+        assert!(report.skipped.iter().any(|name| name.ends_with("BlocksTile")));
+        assert!(!world.has_component::<BlocksTile>(&report.id));
+        assert_eq!(world.get_component::<Position>(&report.id).unwrap().coords(), Vector::new(3, 4));
+        assert_eq!(world.get_resource::<Map>().unwrap().blocker(&Vector::new(3, 4)), Some(&blocker));
+    }
+}
@@ -0,0 +1,87 @@
+use rltk::RandomNumberGenerator;
+
+use crate::{map::{Map, Tile}, vectors::Vector};
+
+use super::{GeneratedMap, MapGenerator, MapLayout, Rect};
+
+/// Recursively splits the map into partitions, places one room in every leaf and joins neighbouring leaves
+pub struct BspGenerator {
+    pub min_leaf: i32,
+    pub min_room: i32,
+    pub max_depth: usize,
+}
+
+impl BspGenerator {
+    pub fn new() -> Self {
+        BspGenerator { min_leaf: 8, min_room: 4, max_depth: 6 }
+    }
+
+    fn split(&self, area: Rect, depth: usize, rng: &mut RandomNumberGenerator, leaves: &mut Vec<Rect>) {
+        let can_split_x = area.width() >= 2 * self.min_leaf;
+        let can_split_y = area.height() >= 2 * self.min_leaf;
+
+        if depth >= self.max_depth || (!can_split_x && !can_split_y) {
+            leaves.push(area);
+            return;
+        }
+
+        // Long partitions are cut across their length so leaves stay roughly square
+        let vertical = match (can_split_x, can_split_y) {
+            (true, false) => true,
+            (false, true) => false,
+            _ if area.width() * 4 > area.height() * 5 => true,
+            _ if area.height() * 4 > area.width() * 5 => false,
+            _ => rng.range(0, 2) == 0,
+        };
+
+        if vertical {
+            let cut = area.min.x + rng.range(self.min_leaf, area.width() - self.min_leaf + 1);
+
+            self.split(Rect { min: area.min, max: Vector::new(cut - 1, area.max.y) }, depth + 1, rng, leaves);
+            self.split(Rect { min: Vector::new(cut, area.min.y), max: area.max }, depth + 1, rng, leaves);
+        } else {
+            let cut = area.min.y + rng.range(self.min_leaf, area.height() - self.min_leaf + 1);
+
+            self.split(Rect { min: area.min, max: Vector::new(area.max.x, cut - 1) }, depth + 1, rng, leaves);
+            self.split(Rect { min: Vector::new(area.min.x, cut), max: area.max }, depth + 1, rng, leaves);
+        }
+    }
+
+    /// A room inside the leaf which leaves a wall of at least one tile to the leaf's edges
+    fn room_in(&self, leaf: &Rect, rng: &mut RandomNumberGenerator) -> Rect {
+        let available = Vector::new((leaf.width() - 2).max(1), (leaf.height() - 2).max(1));
+
+        let w = rng.range(self.min_room.min(available.x), available.x + 1);
+        let h = rng.range(self.min_room.min(available.y), available.y + 1);
+        let x = leaf.min.x + 1 + rng.range(0, available.x - w + 1);
+        let y = leaf.min.y + 1 + rng.range(0, available.y - h + 1);
+
+        Rect::new(x, y, w, h)
+    }
+}
+
+impl MapGenerator for BspGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut RandomNumberGenerator) -> GeneratedMap {
+        let mut map = Map::filled(width, height, Tile::wall());
+        let mut leaves = Vec::new();
+
+        // The root partition includes the border, rooms keep a tile of distance to their leaf which keeps the border intact
+        self.split(Rect::new(0, 0, width as i32, height as i32), 0, rng, &mut leaves);
+
+        let rooms: Vec<Rect> = leaves.iter().map(|leaf| self.room_in(leaf, rng)).collect();
+
+        for room in rooms.iter() {
+            super::carve_room(&mut map, room);
+        }
+
+        // Leaves are collected depth first so consecutive rooms are siblings or close cousins
+        for pair in rooms.windows(2) {
+            super::carve_corridor(&mut map, pair[0].center(), pair[1].center(), rng);
+        }
+
+        let spawn = rooms[0].center();
+        let spawn_points = rooms.iter().skip(1).map(|room| room.center()).collect();
+
+        GeneratedMap { map, layout: MapLayout { spawn, spawn_points, rooms, ..Default::default() } }
+    }
+}
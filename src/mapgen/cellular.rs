@@ -0,0 +1,70 @@
+use rltk::RandomNumberGenerator;
+
+use crate::{map::{Map, Tile}, vectors::Vector};
+
+use super::{GeneratedMap, MapGenerator, MapLayout};
+
+/// Grows caves from random noise, a tile becomes a wall when most of its neighbours are walls
+pub struct CellularGenerator {
+    /// Percentage of tiles which start out as walls
+    pub fill: i32,
+    pub iterations: usize,
+}
+
+impl CellularGenerator {
+    pub fn new() -> Self {
+        CellularGenerator { fill: 45, iterations: 5 }
+    }
+}
+
+impl MapGenerator for CellularGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut RandomNumberGenerator) -> GeneratedMap {
+        let (w, h) = (width as i32, height as i32);
+        let on_border = |x: i32, y: i32| x == 0 || y == 0 || x == w - 1 || y == h - 1;
+
+        let mut walls: Vec<bool> = (0..h)
+            .flat_map(|y| (0..w).map(move |x| (x, y)))
+            .map(|(x, y)| on_border(x, y) || rng.roll_dice(1, 100) <= self.fill)
+            .collect();
+
+        for _i in 0..self.iterations {
+            let previous = walls.clone();
+
+            for y in 1..(h - 1) {
+                for x in 1..(w - 1) {
+                    let neighbours = (-1..=1)
+                        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                        .filter(|&(dx, dy)| (dx, dy) != (0, 0) && previous[((y + dy) * w + x + dx) as usize])
+                        .count();
+
+                    walls[(y * w + x) as usize] = neighbours > 4 || (neighbours == 4 && previous[(y * w + x) as usize]);
+                }
+            }
+        }
+
+        let mut map = Map::empty(width, height);
+
+        for (index, wall) in walls.into_iter().enumerate() {
+            if wall {
+                if let Some(tile) = map.get_mut(&Vector::new(index as i32 % w, index as i32 / w)) {
+                    *tile = Tile::wall();
+                }
+            }
+        }
+
+        let centre = Vector::new(w / 2, h / 2);
+
+        // A map without any open tile gets its centre carved out so there always is somewhere to stand
+        let spawn = super::nearest_walkable(&map, centre).unwrap_or_else(|| {
+            if let Some(tile) = map.get_mut(&centre) {
+                *tile = Tile::ground();
+            }
+
+            centre
+        });
+
+        let spawn_points = super::random_spawn_points(&map, spawn, 10, rng);
+
+        GeneratedMap { map, layout: MapLayout { spawn, spawn_points, rooms: Vec::new(), ..Default::default() } }
+    }
+}
@@ -0,0 +1,65 @@
+use rltk::RandomNumberGenerator;
+
+use crate::{map::{Map, Tile}, vectors::{Vector, UP_VECTOR, DOWN_VECTOR, LEFT_VECTOR, RIGHT_VECTOR}};
+
+use super::{GeneratedMap, MapGenerator, MapLayout};
+
+/// Digs winding tunnels by sending walkers stumbling from already open tiles until enough of the map is open
+pub struct DrunkardGenerator {
+    /// Percentage of the map which should be open
+    pub open: usize,
+    pub steps: usize,
+}
+
+impl DrunkardGenerator {
+    pub fn new() -> Self {
+        DrunkardGenerator { open: 40, steps: 400 }
+    }
+}
+
+impl MapGenerator for DrunkardGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut RandomNumberGenerator) -> GeneratedMap {
+        let mut map = Map::filled(width, height, Tile::wall());
+        let (w, h) = (width as i32, height as i32);
+
+        let spawn = Vector::new(w / 2, h / 2);
+        let target = (width.saturating_sub(2) * height.saturating_sub(2) * self.open / 100).max(1);
+
+        let mut open = vec![spawn];
+
+        if let Some(tile) = map.get_mut(&spawn) {
+            *tile = Tile::ground();
+        }
+
+        // Walkers only start on open tiles so everything dug is connected to the spawn
+        while open.len() < target {
+            let mut position = open[rng.range(0, open.len())];
+
+            for _step in 0..self.steps {
+                let next = position + match rng.range(0, 4) {
+                    0 => RIGHT_VECTOR,
+                    1 => LEFT_VECTOR,
+                    2 => DOWN_VECTOR,
+                    _ => UP_VECTOR,
+                };
+
+                if next.x < 1 || next.y < 1 || next.x > w - 2 || next.y > h - 2 {
+                    continue;
+                }
+
+                position = next;
+
+                if let Some(tile) = map.get_mut(&position) {
+                    if !tile.walkable() {
+                        *tile = Tile::ground();
+                        open.push(position);
+                    }
+                }
+            }
+        }
+
+        let spawn_points = super::random_spawn_points(&map, spawn, 10, rng);
+
+        GeneratedMap { map, layout: MapLayout { spawn, spawn_points, rooms: Vec::new(), ..Default::default() } }
+    }
+}
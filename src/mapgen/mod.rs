@@ -0,0 +1,322 @@
+use rltk::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+
+use crate::{constants, map::{Map, Tile}, vectors::Vector};
+
+use vaults::{vaults, PlacedVault, VaultStage};
+
+pub mod scatter;
+pub mod rooms;
+pub mod bsp;
+pub mod cellular;
+pub mod drunkard;
+pub mod vaults;
+
+/// Builds a map of the given size, the same rng state must always produce the same map
+pub trait MapGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut RandomNumberGenerator) -> GeneratedMap;
+}
+
+/// A rectangle of tiles, both corners are inclusive
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Rect { min: Vector::new(x, y), max: Vector::new(x + width - 1, y + height - 1) }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.max.x - self.min.x + 1
+    }
+
+    pub fn height(&self) -> i32 {
+        self.max.y - self.min.y + 1
+    }
+
+    pub fn center(&self) -> Vector {
+        Vector::center(self.min, self.max)
+    }
+
+    pub fn contains(&self, position: &Vector) -> bool {
+        self.min.x <= position.x && position.x <= self.max.x && self.min.y <= position.y && position.y <= self.max.y
+    }
+
+    /// True if the rectangles overlap or touch, so rooms never share a wall
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.min.x <= other.max.x + 1 && other.min.x <= self.max.x + 1 && self.min.y <= other.max.y + 1 && other.min.y <= self.max.y + 1
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = Vector> + '_ {
+        (self.min.y..=self.max.y).flat_map(move |y| (self.min.x..=self.max.x).map(move |x| Vector::new(x, y)))
+    }
+}
+
+/// What a generator knows about the map it built besides the tiles
+#[derive(Clone, Debug, Default)]
+pub struct MapLayout {
+    pub spawn: Vector,
+    pub spawn_points: Vec<Vector>,
+    pub rooms: Vec<Rect>,
+    /// The number of tiles reachable from the spawn
+    pub main_region: usize,
+    pub up_stairs: Option<Vector>,
+    pub down_stairs: Option<Vector>,
+    pub vaults: Vec<PlacedVault>,
+    /// Entities to spawn once the level is played, by prefab name
+    pub prefabs: Vec<(Vector, String)>,
+}
+
+pub struct GeneratedMap {
+    pub map: Map,
+    pub layout: MapLayout,
+}
+
+impl GeneratedMap {
+    /// Deals with tiles which can not be reached from the spawn and records the size of the spawn's region
+    pub fn connect(&mut self, connectivity: Connectivity, rng: &mut RandomNumberGenerator) {
+        let regions = self.map.regions();
+
+        if let Some(main) = regions.region(&self.layout.spawn) {
+            match connectivity {
+                Connectivity::Keep => (),
+                Connectivity::Cull => {
+                    for region in (0..regions.len()).filter(|region| *region != main) {
+                        for position in regions.tiles(region) {
+                            if let Some(tile) = self.map.get_mut(&position) {
+                                *tile = Tile::wall();
+                            }
+                        }
+                    }
+                },
+                Connectivity::Join => {
+                    let mut reachable: Vec<Vector> = regions.tiles(main).collect();
+
+                    for region in (0..regions.len()).filter(|region| *region != main) {
+                        let Some(from) = regions.tiles(region).next() else { continue };
+
+                        if let Some(to) = reachable.iter().copied().min_by_key(|to| (to.x - from.x).abs() + (to.y - from.y).abs()) {
+                            carve_corridor(&mut self.map, from, to, rng);
+                        }
+
+                        reachable.extend(regions.tiles(region));
+                    }
+                },
+            }
+        }
+
+        // Spawn points, vaults and prefabs cut off from the spawn are useless whether or not the rest of their region was kept
+        let regions = self.map.regions();
+        let main = regions.region(&self.layout.spawn);
+
+        self.layout.spawn_points.retain(|point| regions.region(point) == main);
+        self.layout.vaults.retain(|vault| vault.area.points().any(|position| regions.region(&position) == main));
+        self.layout.prefabs.retain(|(position, _)| regions.region(position) == main);
+        self.layout.main_region = main.map_or(0, |main| regions.size(main));
+    }
+}
+
+/// What happens to walkable tiles which can not be reached from the spawn
+/// Keep: They are left alone
+/// Cull: They are filled with walls
+/// Join: A tunnel is dug from every unreachable region to the closest reachable tile
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Connectivity {
+    Keep,
+    Cull,
+    #[default]
+    Join,
+}
+
+/// The generators which can be picked in the game setup
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum GeneratorKind {
+    Scatter,
+    #[default]
+    RoomsAndCorridors,
+    Bsp,
+    Cellular,
+    DrunkardsWalk,
+}
+
+impl GeneratorKind {
+    pub const ALL: [GeneratorKind; 5] = [
+        GeneratorKind::Scatter,
+        GeneratorKind::RoomsAndCorridors,
+        GeneratorKind::Bsp,
+        GeneratorKind::Cellular,
+        GeneratorKind::DrunkardsWalk,
+    ];
+
+    pub fn generator(&self) -> Box<dyn MapGenerator> {
+        match self {
+            GeneratorKind::Scatter => Box::new(scatter::ScatterGenerator::new()),
+            GeneratorKind::RoomsAndCorridors => Box::new(rooms::RoomsGenerator::new()),
+            GeneratorKind::Bsp => Box::new(bsp::BspGenerator::new()),
+            GeneratorKind::Cellular => Box::new(cellular::CellularGenerator::new()),
+            GeneratorKind::DrunkardsWalk => Box::new(drunkard::DrunkardGenerator::new()),
+        }
+    }
+
+    pub fn generate(&self, width: usize, height: usize, rng: &mut RandomNumberGenerator) -> GeneratedMap {
+        self.generator().generate(width, height, rng)
+    }
+
+    /// Generates a map, stamps vaults into it and makes sure of its connectivity
+    pub fn build(&self, width: usize, height: usize, connectivity: Connectivity, rng: &mut RandomNumberGenerator) -> GeneratedMap {
+        let mut generated = self.generate(width, height, rng);
+
+        VaultStage::new(vaults(), constants::VAULTS_PER_LEVEL).apply(&mut generated, rng);
+
+        generated.connect(connectivity, rng);
+
+        generated
+    }
+}
+
+pub fn carve_room(map: &mut Map, room: &Rect) {
+    for position in room.points() {
+        if let Some(tile) = map.get_mut(&position) {
+            *tile = Tile::ground();
+        }
+    }
+}
+
+/// Carves an L shaped corridor between two points, randomly picking which leg comes first
+pub fn carve_corridor(map: &mut Map, from: Vector, to: Vector, rng: &mut RandomNumberGenerator) {
+    let corner = if rng.range(0, 2) == 0 {
+        Vector::new(to.x, from.y)
+    } else {
+        Vector::new(from.x, to.y)
+    };
+
+    for position in Vector::line(&from, &corner).into_iter().chain(Vector::line(&corner, &to)) {
+        if let Some(tile) = map.get_mut(&position) {
+            *tile = Tile::ground();
+        }
+    }
+}
+
+/// The walkable tile closest to the target, ties are broken by scan order
+pub fn nearest_walkable(map: &Map, target: Vector) -> Option<Vector> {
+    walkable_tiles(map).min_by_key(|position| {
+        let delta = *position - target;
+        delta.x * delta.x + delta.y * delta.y
+    })
+}
+
+pub fn walkable_tiles(map: &Map) -> impl Iterator<Item = Vector> + '_ {
+    (0..map.height as i32)
+        .flat_map(move |y| (0..map.width as i32).map(move |x| Vector::new(x, y)))
+        .filter(move |position| map.get(position).is_some_and(|tile| tile.walkable()))
+}
+
+/// Picks up to `count` distinct walkable tiles other than the spawn
+pub fn random_spawn_points(map: &Map, spawn: Vector, count: usize, rng: &mut RandomNumberGenerator) -> Vec<Vector> {
+    let mut candidates: Vec<Vector> = walkable_tiles(map).filter(|position| *position != spawn).collect();
+    let mut points = Vec::with_capacity(count.min(candidates.len()));
+
+    while points.len() < count && !candidates.is_empty() {
+        let index = rng.range(0, candidates.len());
+        points.push(candidates.swap_remove(index));
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use rltk::RandomNumberGenerator;
+
+    use crate::{map::{Map, Tile}, vectors::Vector};
+
+    use super::{vaults::{Orientation, PlacedVault}, Connectivity, GeneratedMap, GeneratorKind, MapLayout, Rect, walkable_tiles};
+
+    fn passable_tiles(map: &crate::map::Map) -> usize {
+        (0..map.height as i32).flat_map(|y| (0..map.width as i32).map(move |x| Vector::new(x, y))).filter(|position| map.get(position).is_some_and(|tile| tile.passable())).count()
+    }
+
+    #[test]
+    fn test_generators_keep_border_and_spawn() {
+        for kind in GeneratorKind::ALL {
+            let generated = kind.generate(80, 50, &mut RandomNumberGenerator::seeded(7));
+            let map = &generated.map;
+
+            assert!(map.get(&generated.layout.spawn).is_some_and(|tile| tile.walkable()), "{:?} spawns on a wall", kind);
+
+            for point in generated.layout.spawn_points.iter() {
+                assert!(map.get(point).is_some_and(|tile| tile.walkable()), "{:?} has a spawn point on a wall", kind);
+            }
+
+            for x in 0..80 {
+                assert!(!map.get(&Vector::new(x, 0)).unwrap().walkable(), "{:?} broke the border", kind);
+                assert!(!map.get(&Vector::new(x, 49)).unwrap().walkable(), "{:?} broke the border", kind);
+            }
+
+            for y in 0..50 {
+                assert!(!map.get(&Vector::new(0, y)).unwrap().walkable(), "{:?} broke the border", kind);
+                assert!(!map.get(&Vector::new(79, y)).unwrap().walkable(), "{:?} broke the border", kind);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generators_are_deterministic() {
+        for kind in GeneratorKind::ALL {
+            let a = kind.generate(60, 40, &mut RandomNumberGenerator::seeded(42));
+            let b = kind.generate(60, 40, &mut RandomNumberGenerator::seeded(42));
+
+            assert!(walkable_tiles(&a.map).eq(walkable_tiles(&b.map)), "{:?} is not deterministic", kind);
+            assert_eq!(a.layout.spawn, b.layout.spawn);
+            assert_eq!(a.layout.rooms, b.layout.rooms);
+        }
+    }
+
+    #[test]
+    fn test_every_walkable_tile_is_reachable() {
+        for kind in GeneratorKind::ALL {
+            for connectivity in [Connectivity::Cull, Connectivity::Join] {
+                for seed in 0..4 {
+                    let generated = kind.build(80, 50, connectivity, &mut RandomNumberGenerator::seeded(seed));
+                    let regions = generated.map.regions();
+                    let main = regions.region(&generated.layout.spawn).unwrap();
+
+                    for position in walkable_tiles(&generated.map) {
+                        assert_eq!(regions.region(&position), Some(main), "{:?} with {:?} left {} unreachable", kind, connectivity, position);
+                    }
+
+                    assert_eq!(generated.layout.main_region, passable_tiles(&generated.map));
+                    assert!(generated.layout.prefabs.iter().all(|(position, _)| regions.region(position) == Some(main)));
+                    assert!(generated.layout.vaults.iter().all(|vault| vault.area.points().any(|position| regions.region(&position) == Some(main))));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_culled_vaults_are_dropped() {
+        let mut map = Map::filled(20, 10, Tile::wall());
+
+        for position in Rect::new(1, 1, 7, 8).points().chain(Rect::new(12, 1, 7, 8).points()) {
+            *map.get_mut(&position).unwrap() = Tile::ground();
+        }
+
+        let vault = |name: &str, area: Rect| PlacedVault { name: name.into(), area, orientation: Orientation::default() };
+        let layout = MapLayout {
+            spawn: Vector::new(2, 2),
+            vaults: vec![vault("kept", Rect::new(4, 4, 3, 3)), vault("culled", Rect::new(14, 4, 3, 3))],
+            prefabs: vec![(Vector::new(5, 5), "brazier".into()), (Vector::new(15, 5), "brazier".into())],
+            ..Default::default()
+        };
+
+        let mut generated = GeneratedMap { map, layout };
+        generated.connect(Connectivity::Cull, &mut RandomNumberGenerator::seeded(0));
+
+        assert!(!generated.map.get(&Vector::new(15, 5)).unwrap().walkable());
+        assert_eq!(generated.layout.vaults.iter().map(|vault| vault.name.as_str()).collect::<Vec<_>>(), vec!["kept"]);
+        assert_eq!(generated.layout.prefabs, vec![(Vector::new(5, 5), "brazier".to_string())]);
+    }
+}
@@ -0,0 +1,65 @@
+use rltk::RandomNumberGenerator;
+
+use crate::{map::{Map, Tile}, vectors::Vector};
+
+use super::{GeneratedMap, MapGenerator, MapLayout, Rect};
+
+/// Places non overlapping rectangular rooms at random and joins each to the previous one with a corridor
+pub struct RoomsGenerator {
+    pub max_rooms: usize,
+    pub min_size: i32,
+    pub max_size: i32,
+}
+
+impl RoomsGenerator {
+    pub fn new() -> Self {
+        RoomsGenerator { max_rooms: 30, min_size: 6, max_size: 10 }
+    }
+}
+
+impl MapGenerator for RoomsGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut RandomNumberGenerator) -> GeneratedMap {
+        let mut map = Map::filled(width, height, Tile::wall());
+        let mut rooms: Vec<Rect> = Vec::new();
+
+        // Rooms are kept one tile away from the edge so the border stays intact
+        let max_width = self.max_size.min(width as i32 - 2);
+        let max_height = self.max_size.min(height as i32 - 2);
+
+        if max_width >= 1 && max_height >= 1 {
+            for _i in 0..self.max_rooms {
+                let w = rng.range(self.min_size.min(max_width), max_width + 1);
+                let h = rng.range(self.min_size.min(max_height), max_height + 1);
+                let x = rng.range(1, width as i32 - w);
+                let y = rng.range(1, height as i32 - h);
+
+                let room = Rect::new(x, y, w, h);
+
+                if rooms.iter().any(|other| other.intersects(&room)) {
+                    continue;
+                }
+
+                super::carve_room(&mut map, &room);
+
+                if let Some(previous) = rooms.last() {
+                    super::carve_corridor(&mut map, previous.center(), room.center(), rng);
+                }
+
+                rooms.push(room);
+            }
+        }
+
+        let spawn = match rooms.first() {
+            Some(room) => room.center(),
+            None => {
+                let centre = Vector::new((width / 2) as i32, (height / 2) as i32);
+                super::carve_room(&mut map, &Rect::new(centre.x, centre.y, 1, 1));
+                centre
+            }
+        };
+
+        let spawn_points = rooms.iter().skip(1).map(|room| room.center()).collect();
+
+        GeneratedMap { map, layout: MapLayout { spawn, spawn_points, rooms, ..Default::default() } }
+    }
+}
@@ -0,0 +1,59 @@
+use rltk::RandomNumberGenerator;
+
+use crate::{map::{Map, Tile}, vectors::Vector};
+
+use super::{GeneratedMap, MapGenerator, MapLayout};
+
+/// Walls in the border and scatters single wall tiles over a fifth of the map, only the centre is kept clear
+pub struct ScatterGenerator {}
+
+impl ScatterGenerator {
+    pub fn new() -> Self {
+        ScatterGenerator {}
+    }
+}
+
+impl MapGenerator for ScatterGenerator {
+    fn generate(&self, width: usize, height: usize, rng: &mut RandomNumberGenerator) -> GeneratedMap {
+        let mut map = Map::empty(width, height);
+
+        for x in 0..(width as i32) {
+            if let Some(tile) = map.get_mut(&Vector::from((x, 0))) {
+                *tile = Tile::wall();
+            }
+
+            if let Some(tile) = map.get_mut(&Vector::from((x, height as i32 - 1))) {
+                *tile = Tile::wall();
+            }
+        }
+
+        for y in 0..(height as i32) {
+            if let Some(tile) = map.get_mut(&Vector::from((0, y))) {
+                *tile = Tile::wall();
+            }
+
+            if let Some(tile) = map.get_mut(&Vector::from((width as i32 - 1, y))) {
+                *tile = Tile::wall();
+            }
+        }
+
+        let middle = Vector::new((width / 2) as i32, (height / 2) as i32);
+
+        for _i in 0..(width * height / 5) {
+            let x = rng.roll_dice(1, width as i32 - 1);
+            let y = rng.roll_dice(1, height as i32 - 1);
+
+            let pos = Vector::new(x, y);
+
+            if pos != middle {
+                if let Some(tile) = map.get_mut(&pos) {
+                    *tile = Tile::wall();
+                }
+            }
+        }
+
+        let spawn_points = super::random_spawn_points(&map, middle, 10, rng);
+
+        GeneratedMap { map, layout: MapLayout { spawn: middle, spawn_points, rooms: Vec::new(), ..Default::default() } }
+    }
+}
@@ -0,0 +1,403 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use rltk::RandomNumberGenerator;
+
+use crate::{ecs::entity::Entity, entities, map::Tile, tiles::{tile_types, TileTypeId}, vectors::{Vector, UP_VECTOR, DOWN_VECTOR, LEFT_VECTOR, RIGHT_VECTOR}};
+
+use super::{GeneratedMap, Rect};
+
+/// Layout cells holding a space leave the map as it is, so vaults need not be rectangular
+const UNTOUCHED: char = ' ';
+
+static VAULTS: OnceLock<Vec<Vault>> = OnceLock::new();
+
+/// The vaults loaded from `raws/vaults/*.txt`, in file name order
+pub fn vaults() -> &'static [Vault] {
+    VAULTS.get_or_init(|| {
+        let mut files: Vec<_> = crate::RAWS.get_dir("vaults")
+            .map(|dir| dir.files().filter(|file| file.path().extension().is_some_and(|extension| extension == "txt")).collect())
+            .unwrap_or_default();
+
+        files.sort_by_key(|file| file.path());
+
+        files.iter().map(|file| {
+            Vault::parse(file.contents_utf8().unwrap()).unwrap_or_else(|error| panic!("vault {} is invalid: {:?}", file.path().display(), error))
+        }).collect()
+    })
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum VaultError {
+    MissingName,
+    MissingLayout,
+    InvalidLine(String),
+    UnknownTile(String),
+    UnknownPrefab(String),
+    UnknownSymbol(char),
+}
+
+/// What a layout character stands for, a tile and optionally an entity placed on it
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LegendEntry {
+    pub tile: TileTypeId,
+    pub prefab: Option<String>,
+}
+
+/// A hand authored room, read from a text file such as:
+/// ```text
+/// name: shrine
+/// weight: 2
+/// legend:
+/// #: wall
+/// b: ground brazier
+/// layout:
+/// ###
+/// #b#
+/// ```
+/// A legend line maps a character to a tile type and an optional entity prefab
+pub struct Vault {
+    pub name: String,
+    /// How likely the vault is to be picked relative to the others
+    pub weight: u32,
+    legend: HashMap<char, LegendEntry>,
+    rows: Vec<Vec<char>>,
+}
+
+enum Section {
+    Header,
+    Legend,
+    Layout,
+}
+
+impl Vault {
+    pub fn parse(text: &str) -> Result<Self, VaultError> {
+        let mut name = None;
+        let mut weight = 1;
+        let mut legend = HashMap::new();
+        let mut rows: Vec<Vec<char>> = Vec::new();
+        let mut section = Section::Header;
+
+        for line in text.lines() {
+            let line = line.trim_end_matches('\r');
+
+            match section {
+                Section::Layout => rows.push(line.chars().collect()),
+                _ if line.trim().is_empty() => (),
+                _ if line.trim() == "legend:" => section = Section::Legend,
+                _ if line.trim() == "layout:" => section = Section::Layout,
+                Section::Header => {
+                    let (key, value) = line.split_once(':').ok_or_else(|| VaultError::InvalidLine(line.into()))?;
+
+                    match key.trim() {
+                        "name" => name = Some(value.trim().to_string()),
+                        "weight" => weight = value.trim().parse().map_err(|_| VaultError::InvalidLine(line.into()))?,
+                        _ => return Err(VaultError::InvalidLine(line.into())),
+                    }
+                },
+                Section::Legend => {
+                    let mut chars = line.chars();
+
+                    let (Some(symbol), Some(':')) = (chars.next(), chars.next()) else {
+                        return Err(VaultError::InvalidLine(line.into()));
+                    };
+
+                    let mut words = chars.as_str().split_whitespace();
+
+                    let tile = words.next().ok_or_else(|| VaultError::InvalidLine(line.into()))?;
+                    let tile = tile_types().id(tile).ok_or_else(|| VaultError::UnknownTile(tile.into()))?;
+                    let prefab = words.next().map(|prefab| prefab.to_string());
+
+                    if symbol == UNTOUCHED || words.next().is_some() {
+                        return Err(VaultError::InvalidLine(line.into()));
+                    }
+
+                    if let Some(prefab) = prefab.as_ref().filter(|prefab| entities::prefab(Ok(Entity::new()), prefab, 0, 0).is_none()) {
+                        return Err(VaultError::UnknownPrefab(prefab.clone()));
+                    }
+
+                    legend.insert(symbol, LegendEntry { tile, prefab });
+                },
+            }
+        }
+
+        while rows.last().is_some_and(|row| row.iter().all(|symbol| *symbol == UNTOUCHED)) {
+            rows.pop();
+        }
+
+        if rows.is_empty() {
+            return Err(VaultError::MissingLayout);
+        }
+
+        if let Some(symbol) = rows.iter().flatten().find(|symbol| **symbol != UNTOUCHED && !legend.contains_key(symbol)) {
+            return Err(VaultError::UnknownSymbol(*symbol));
+        }
+
+        // Short rows are padded so every row has the same width
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+
+        for row in rows.iter_mut() {
+            row.resize(width, UNTOUCHED);
+        }
+
+        Ok(Vault { name: name.ok_or(VaultError::MissingName)?, weight, legend, rows })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.rows.first().map_or(0, |row| row.len() as i32)
+    }
+
+    pub fn height(&self) -> i32 {
+        self.rows.len() as i32
+    }
+
+    /// The size of the vault once oriented
+    pub fn size(&self, orientation: Orientation) -> Vector {
+        match orientation.rotation % 2 {
+            0 => Vector::new(self.width(), self.height()),
+            _ => Vector::new(self.height(), self.width()),
+        }
+    }
+
+    /// Every cell which changes the map as an offset into the oriented vault
+    pub fn cells(&self, orientation: Orientation) -> impl Iterator<Item = (Vector, &LegendEntry)> + '_ {
+        self.rows.iter().enumerate().flat_map(move |(y, row)| {
+            row.iter().enumerate().filter_map(move |(x, symbol)| {
+                let entry = self.legend.get(symbol)?;
+
+                Some((orientation.apply(Vector::new(x as i32, y as i32), self.width(), self.height()), entry))
+            })
+        })
+    }
+}
+
+/// A vault is mirrored horizontally first and then rotated clockwise by quarter turns
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Orientation {
+    pub rotation: u8,
+    pub mirrored: bool,
+}
+
+impl Orientation {
+    pub fn new(rotation: u8, mirrored: bool) -> Self {
+        Orientation { rotation: rotation % 4, mirrored }
+    }
+
+    pub fn random(rng: &mut RandomNumberGenerator) -> Self {
+        Orientation::new(rng.range(0, 4), rng.range(0, 2) == 1)
+    }
+
+    /// Maps a cell of a vault of the given size to its place in the oriented vault
+    pub fn apply(&self, cell: Vector, width: i32, height: i32) -> Vector {
+        let x = if self.mirrored { width - 1 - cell.x } else { cell.x };
+        let y = cell.y;
+
+        match self.rotation % 4 {
+            0 => Vector::new(x, y),
+            1 => Vector::new(height - 1 - y, x),
+            2 => Vector::new(width - 1 - x, height - 1 - y),
+            _ => Vector::new(y, width - 1 - x),
+        }
+    }
+}
+
+/// A vault stamped into a map, the rectangle covers all of it
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PlacedVault {
+    pub name: String,
+    pub area: Rect,
+    pub orientation: Orientation,
+}
+
+/// Stamps up to `count` vaults into a generated map, to be run before the map's connectivity is dealt with
+/// A vault is only placed inside the border, away from other vaults and the spawn, with a passable edge such as a door opening onto walkable tiles of the map
+pub struct VaultStage<'v> {
+    pub vaults: &'v [Vault],
+    pub count: usize,
+    pub attempts: usize,
+}
+
+impl <'v> VaultStage<'v> {
+    pub fn new(vaults: &'v [Vault], count: usize) -> Self {
+        VaultStage { vaults, count, attempts: 40 }
+    }
+
+    /// Returns the vaults placed, they are also added to the map's layout along with their prefabs
+    pub fn apply(&self, generated: &mut GeneratedMap, rng: &mut RandomNumberGenerator) -> Vec<PlacedVault> {
+        let total: u32 = self.vaults.iter().map(|vault| vault.weight).sum();
+        let mut placed = Vec::new();
+
+        if total == 0 {
+            return placed;
+        }
+
+        for _ in 0..self.count {
+            let mut roll = rng.range(0, total);
+            let Some(vault) = self.vaults.iter().find(|vault| {
+                if roll < vault.weight {
+                    return true;
+                }
+
+                roll -= vault.weight;
+                false
+            }) else {
+                continue;
+            };
+
+            let orientation = Orientation::random(rng);
+
+            if let Some(area) = self.find_area(generated, vault, orientation, rng) {
+                stamp(generated, vault, orientation, area);
+
+                let placement = PlacedVault { name: vault.name.clone(), area, orientation };
+
+                generated.layout.vaults.push(placement.clone());
+                placed.push(placement);
+            }
+        }
+
+        placed
+    }
+
+    fn find_area(&self, generated: &GeneratedMap, vault: &Vault, orientation: Orientation, rng: &mut RandomNumberGenerator) -> Option<Rect> {
+        let map = &generated.map;
+        let size = vault.size(orientation);
+
+        // The border is never stamped over
+        let (max_x, max_y) = (map.width as i32 - 1 - size.x, map.height as i32 - 1 - size.y);
+
+        if max_x < 1 || max_y < 1 {
+            return None;
+        }
+
+        (0..self.attempts).find_map(|_| {
+            let area = Rect::new(rng.range(1, max_x + 1), rng.range(1, max_y + 1), size.x, size.y);
+
+            // The tiles inside are stamped over, so only the map around the vault decides whether it can be entered
+            let opens = vault.cells(orientation)
+                .filter(|(_, entry)| Tile::new(entry.tile).passable())
+                .map(|(offset, _)| area.min + offset)
+                .any(|position| [UP_VECTOR, DOWN_VECTOR, LEFT_VECTOR, RIGHT_VECTOR].iter().any(|step| {
+                    let outside = position + *step;
+
+                    !area.contains(&outside) && map.get(&outside).is_some_and(|tile| tile.walkable())
+                }));
+
+            let valid = opens
+                && !area.contains(&generated.layout.spawn)
+                && !generated.layout.vaults.iter().any(|other| other.area.intersects(&area));
+
+            valid.then_some(area)
+        })
+    }
+}
+
+fn stamp(generated: &mut GeneratedMap, vault: &Vault, orientation: Orientation, area: Rect) {
+    for (offset, entry) in vault.cells(orientation) {
+        let position = area.min + offset;
+
+        if let Some(tile) = generated.map.get_mut(&position) {
+            *tile = Tile::new(entry.tile);
+        }
+
+        if let Some(prefab) = entry.prefab.as_ref() {
+            generated.layout.prefabs.push((position, prefab.clone()));
+        }
+    }
+
+    // The vault decides what stands inside it
+    generated.layout.spawn_points.retain(|point| !area.contains(point));
+}
+
+#[cfg(test)]
+mod tests {
+    use rltk::RandomNumberGenerator;
+
+    use crate::{map::{Map, Tile}, mapgen::{GeneratedMap, MapLayout}, tiles, vectors::Vector};
+
+    use super::{vaults, Orientation, Vault, VaultError, VaultStage};
+
+    const CELL: &str = "name: cell\nweight: 3\nlegend:\n#: wall\n.: ground\nb: ground brazier\nlayout:\n###\n#b.\n #\n";
+
+    #[test]
+    fn test_parse_vaults() {
+        let vault = Vault::parse(CELL).unwrap();
+
+        assert_eq!((vault.name.as_str(), vault.weight, vault.width(), vault.height()), ("cell", 3, 3, 3));
+        assert_eq!(vault.cells(Orientation::default()).count(), 7);
+        assert_eq!(vault.cells(Orientation::default()).filter(|(_, entry)| entry.prefab.is_some()).count(), 1);
+
+        assert_eq!(Vault::parse("legend:\n#: wall\nlayout:\n#").err(), Some(VaultError::MissingName));
+        assert_eq!(Vault::parse("name: empty\nlayout:\n").err(), Some(VaultError::MissingLayout));
+        assert_eq!(Vault::parse("name: bad\nlegend:\n#: lava\nlayout:\n#").err(), Some(VaultError::UnknownTile("lava".into())));
+        assert_eq!(Vault::parse("name: bad\nlegend:\n#: wall dragon\nlayout:\n#").err(), Some(VaultError::UnknownPrefab("dragon".into())));
+        assert_eq!(Vault::parse("name: bad\nlegend:\n#: wall\nlayout:\n#x").err(), Some(VaultError::UnknownSymbol('x')));
+
+        assert!(!vaults().is_empty());
+    }
+
+    #[test]
+    fn test_orientations() {
+        let vault = Vault::parse(CELL).unwrap();
+        let brazier = |orientation: Orientation| vault.cells(orientation).find(|(_, entry)| entry.prefab.is_some()).unwrap().0;
+
+        assert_eq!(brazier(Orientation::new(0, false)), Vector::new(1, 1));
+        assert_eq!(brazier(Orientation::new(0, true)), Vector::new(1, 1));
+
+        // The open side on the right of the middle row turns with the vault
+        let open = |orientation: Orientation| vault.cells(orientation).find(|(_, entry)| entry.tile == tiles::tile_types().id(tiles::GROUND).unwrap() && entry.prefab.is_none()).unwrap().0;
+
+        assert_eq!(open(Orientation::new(0, false)), Vector::new(2, 1));
+        assert_eq!(open(Orientation::new(1, false)), Vector::new(1, 2));
+        assert_eq!(open(Orientation::new(2, false)), Vector::new(0, 1));
+        assert_eq!(open(Orientation::new(3, false)), Vector::new(1, 0));
+        assert_eq!(open(Orientation::new(0, true)), Vector::new(0, 1));
+        assert_eq!(open(Orientation::new(5, false)), Vector::new(1, 2));
+
+        let wide = Vault::parse("name: wide\nlegend:\n#: wall\nlayout:\n####").unwrap();
+        assert_eq!(wide.size(Orientation::new(1, true)), Vector::new(1, 4));
+    }
+
+    #[test]
+    fn test_vault_stage_places_vaults() {
+        let vault = Vault::parse(CELL).unwrap();
+        let vaults = [vault];
+
+        let spawn = Vector::new(10, 10);
+        let mut generated = GeneratedMap { map: Map::empty(20, 20), layout: MapLayout { spawn, spawn_points: vec![Vector::new(2, 2)], ..Default::default() } };
+
+        let placed = VaultStage::new(&vaults, 4).apply(&mut generated, &mut RandomNumberGenerator::seeded(3));
+
+        assert!(!placed.is_empty());
+        assert_eq!(generated.layout.vaults, placed);
+        assert_eq!(generated.layout.prefabs.len(), placed.len());
+
+        for (i, vault) in placed.iter().enumerate() {
+            assert!(!vault.area.contains(&spawn) && vault.area.min.x >= 1 && vault.area.max.x <= 18);
+            assert!(placed.iter().skip(i + 1).all(|other| !other.area.intersects(&vault.area)));
+            assert!(vault.area.points().any(|position| generated.map.get(&position) == Some(&Tile::wall())));
+        }
+
+        for (position, prefab) in generated.layout.prefabs.iter() {
+            assert_eq!(prefab, "brazier");
+            assert!(placed.iter().any(|vault| vault.area.contains(position)));
+        }
+
+        assert!(generated.layout.spawn_points.iter().all(|point| placed.iter().all(|vault| !vault.area.contains(point))));
+
+        // Nothing is placed in solid rock
+        let mut solid = GeneratedMap { map: Map::filled(20, 20, Tile::wall()), layout: MapLayout::default() };
+        assert!(VaultStage::new(&vaults, 4).apply(&mut solid, &mut RandomNumberGenerator::seeded(3)).is_empty());
+    }
+
+    #[test]
+    fn test_vaults_open_through_doors() {
+        let closet = Vault::parse("name: closet\nlegend:\n#: wall\n.: ground\n+: door_closed\nlayout:\n#+#\n#.#\n###").unwrap();
+        let vaults = [closet];
+
+        let mut generated = GeneratedMap { map: Map::empty(20, 20), layout: MapLayout { spawn: Vector::new(10, 10), ..Default::default() } };
+        let placed = VaultStage::new(&vaults, 1).apply(&mut generated, &mut RandomNumberGenerator::seeded(3));
+
+        assert_eq!(placed.len(), 1);
+        assert!(placed[0].area.points().any(|position| generated.map.get(&position) == Some(&Tile::of("door_closed"))));
+    }
+}
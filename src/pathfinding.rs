@@ -0,0 +1,320 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::{map::Map, tiles::tile_types, vectors::{Vector, UP_VECTOR, DOWN_VECTOR, LEFT_VECTOR, RIGHT_VECTOR}};
+
+/// The steps an entity can take, movement is never diagonal
+const STEPS: [Vector; 4] = [UP_VECTOR, DOWN_VECTOR, LEFT_VECTOR, RIGHT_VECTOR];
+
+/// The cost of entering a passable tile, as given by its type, closed doors are opened on the way
+pub fn terrain_cost(map: &Map, position: &Vector) -> Option<f32> {
+    map.get(position).filter(|tile| tile.passable()).map(|tile| tile.movement_cost())
+}
+
+/// The lowest cost `terrain_cost` can give a tile, the lower bound `find_path` needs for it
+pub fn min_terrain_cost() -> f32 {
+    tile_types().iter().map(|(_, tile_type)| tile_type.movement_cost).fold(f32::INFINITY, f32::min).max(0.0)
+}
+
+/// Like `terrain_cost` but tiles blocked by an entity can not be entered, except for the goal which is usually blocked by the target itself
+pub fn unoccupied_cost(goal: Vector) -> impl Fn(&Map, &Vector) -> Option<f32> {
+    move |map, position| {
+        if *position != goal && map.blocker(position).is_some() {
+            return None;
+        }
+
+        terrain_cost(map, position)
+    }
+}
+
+/// A tile waiting to be expanded, ordered so the cheapest one is popped first from a max heap
+struct Frontier {
+    priority: f32,
+    index: usize,
+}
+
+impl PartialEq for Frontier {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Frontier {}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.total_cmp(&self.priority).then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+/// Distances from a set of sources to every tile, following the cheapest route
+pub struct DijkstraMap {
+    width: usize,
+    height: usize,
+    distances: Vec<f32>,
+}
+
+impl DijkstraMap {
+    /// The distance to the closest source, None if the tile can not be reached
+    pub fn get(&self, position: &Vector) -> Option<f32> {
+        if position.x < 0 || position.y < 0 || position.x >= self.width as i32 || position.y >= self.height as i32 {
+            return None;
+        }
+
+        Some(self.distances[position.y as usize * self.width + position.x as usize]).filter(|distance| distance.is_finite())
+    }
+
+    /// The neighbour closest to a source, None if no neighbour is closer than the position itself
+    pub fn next_step(&self, from: &Vector) -> Option<Vector> {
+        let current = self.get(from).unwrap_or(f32::INFINITY);
+
+        STEPS.iter()
+            .map(|step| *from + *step)
+            .filter_map(|position| Some((position, self.get(&position)?)))
+            .filter(|(_, distance)| *distance < current)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(position, _)| position)
+    }
+
+    /// A map whose `next_step` leads away from the sources, preferring open areas over dead ends
+    /// The factor should be above 1, the higher it is the more the fleeing entity risks passing its pursuer to get further away
+    pub fn flee<F: Fn(&Map, &Vector) -> Option<f32>>(&self, map: &Map, factor: f32, cost: F) -> DijkstraMap {
+        let seeds: Vec<(Vector, f32)> = (0..self.distances.len())
+            .filter(|index| self.distances[*index].is_finite())
+            .map(|index| (Vector::new((index % self.width) as i32, (index / self.width) as i32), -factor * self.distances[index]))
+            .collect();
+
+        map.dijkstra_seeded(&seeds, cost)
+    }
+}
+
+impl Map {
+    /// The cheapest path from start to goal under the cost function, which returns None for tiles which can not be entered
+    /// The cost must never be below min_step_cost for any tile or the path found may not be the cheapest, a min_step_cost of 0 is always safe
+    /// The path excludes the start and ends on the goal, it is empty if the goal can not be reached
+    pub fn find_path<F: Fn(&Map, &Vector) -> Option<f32>>(&self, start: Vector, goal: Vector, min_step_cost: f32, cost: F) -> Vec<Vector> {
+        if !self.in_bounds(&start) || !self.in_bounds(&goal) || start == goal {
+            return Vec::new();
+        }
+
+        // The heuristic must never overestimate so it assumes every remaining step costs the least a step can
+        let heuristic = |position: &Vector| ((position.x - goal.x).abs() + (position.y - goal.y).abs()) as f32 * min_step_cost.max(0.0);
+
+        let index = |position: &Vector| position.y as usize * self.width + position.x as usize;
+        let position = |index: usize| Vector::new((index % self.width) as i32, (index / self.width) as i32);
+
+        let mut costs = vec![f32::INFINITY; self.width * self.height];
+        let mut came_from: Vec<Option<usize>> = vec![None; self.width * self.height];
+        let mut frontier = BinaryHeap::new();
+
+        costs[index(&start)] = 0.0;
+        frontier.push(Frontier { priority: heuristic(&start), index: index(&start) });
+
+        while let Some(Frontier { priority, index: current }) = frontier.pop() {
+            let here = position(current);
+
+            if here == goal {
+                let mut path = vec![goal];
+                let mut step = current;
+
+                while let Some(previous) = came_from[step] {
+                    if previous == index(&start) {
+                        break;
+                    }
+
+                    path.push(position(previous));
+                    step = previous;
+                }
+
+                path.reverse();
+
+                return path;
+            }
+
+            // Stale entries are left in the heap rather than updated in place
+            if priority > costs[current] + heuristic(&here) {
+                continue;
+            }
+
+            for step in STEPS {
+                let next = here + step;
+
+                if !self.in_bounds(&next) {
+                    continue;
+                }
+
+                if let Some(step_cost) = cost(self, &next) {
+                    let next_cost = costs[current] + step_cost;
+                    let next_index = index(&next);
+
+                    if next_cost < costs[next_index] {
+                        costs[next_index] = next_cost;
+                        came_from[next_index] = Some(current);
+                        frontier.push(Frontier { priority: next_cost + heuristic(&next), index: next_index });
+                    }
+                }
+            }
+        }
+
+        Vec::new()
+    }
+
+    /// Distances from the closest of the sources to every tile under the cost function
+    pub fn dijkstra<F: Fn(&Map, &Vector) -> Option<f32>>(&self, sources: &[Vector], cost: F) -> DijkstraMap {
+        let seeds: Vec<(Vector, f32)> = sources.iter().map(|source| (*source, 0.0)).collect();
+
+        self.dijkstra_seeded(&seeds, cost)
+    }
+
+    /// Like `dijkstra` but every source starts out at its own distance
+    pub fn dijkstra_seeded<F: Fn(&Map, &Vector) -> Option<f32>>(&self, seeds: &[(Vector, f32)], cost: F) -> DijkstraMap {
+        let mut distances = vec![f32::INFINITY; self.width * self.height];
+        let mut frontier = BinaryHeap::new();
+
+        for (source, distance) in seeds {
+            if self.in_bounds(source) {
+                let index = source.y as usize * self.width + source.x as usize;
+
+                if *distance < distances[index] {
+                    distances[index] = *distance;
+                    frontier.push(Frontier { priority: *distance, index });
+                }
+            }
+        }
+
+        while let Some(Frontier { priority, index }) = frontier.pop() {
+            if priority > distances[index] {
+                continue;
+            }
+
+            let here = Vector::new((index % self.width) as i32, (index / self.width) as i32);
+
+            for step in STEPS {
+                let next = here + step;
+
+                if !self.in_bounds(&next) {
+                    continue;
+                }
+
+                if let Some(step_cost) = cost(self, &next) {
+                    let next_index = next.y as usize * self.width + next.x as usize;
+                    let next_distance = priority + step_cost;
+
+                    if next_distance < distances[next_index] {
+                        distances[next_index] = next_distance;
+                        frontier.push(Frontier { priority: next_distance, index: next_index });
+                    }
+                }
+            }
+        }
+
+        DijkstraMap { width: self.width, height: self.height, distances }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::{ecs::{archetype::Archetype, entity::EntityId}, map::{Map, Tile}, vectors::Vector};
+
+    use super::{min_terrain_cost, terrain_cost, unoccupied_cost};
+
+    fn path_cost(map: &Map, path: &[Vector]) -> f32 {
+        path.iter().map(|position| terrain_cost(map, position).unwrap()).sum()
+    }
+
+    #[test]
+    fn test_paths_respect_walls_costs_and_occupancy() {
+        let mut map = Map::empty(7, 5);
+
+        for y in 0..4 {
+            *map.get_mut(&Vector::new(3, y)).unwrap() = Tile::wall();
+        }
+
+        let path = map.find_path(Vector::new(1, 1), Vector::new(5, 1), min_terrain_cost(), terrain_cost);
+        assert_eq!(path.len(), 10);
+        assert_eq!(path.last(), Some(&Vector::new(5, 1)));
+        assert!(path.contains(&Vector::new(3, 4)));
+
+        *map.get_mut(&Vector::new(2, 4)).unwrap() = Tile::of("shallow_water");
+        let path = map.find_path(Vector::new(1, 1), Vector::new(5, 1), min_terrain_cost(), terrain_cost);
+        assert_eq!(path_cost(&map, &path), 11.0);
+
+        map.set_blocker(&Vector::new(3, 4), &EntityId::new(Archetype::new(), 0));
+        assert!(map.find_path(Vector::new(1, 1), Vector::new(5, 1), min_terrain_cost(), unoccupied_cost(Vector::new(5, 1))).is_empty());
+        assert!(!map.find_path(Vector::new(1, 1), Vector::new(5, 1), min_terrain_cost(), terrain_cost).is_empty());
+    }
+
+    #[test]
+    fn test_paths_reach_an_occupied_goal_through_doors() {
+        let mut map = Map::empty(7, 3);
+
+        for y in 0..3 {
+            *map.get_mut(&Vector::new(3, y)).unwrap() = Tile::wall();
+        }
+
+        *map.get_mut(&Vector::new(3, 1)).unwrap() = Tile::of("door_closed");
+        map.set_blocker(&Vector::new(5, 1), &EntityId::new(Archetype::new(), 0));
+
+        let path = map.find_path(Vector::new(1, 1), Vector::new(5, 1), min_terrain_cost(), unoccupied_cost(Vector::new(5, 1)));
+        assert_eq!(path, vec![Vector::new(2, 1), Vector::new(3, 1), Vector::new(4, 1), Vector::new(5, 1)]);
+
+        assert_eq!(map.find_path(Vector::new(1, 1), Vector::new(6, 1), min_terrain_cost(), unoccupied_cost(Vector::new(6, 1))).len(), 7);
+    }
+
+    #[test]
+    fn test_dijkstra_approach_and_flee() {
+        let map = Map::empty(9, 3);
+        let goals = map.dijkstra(&[Vector::new(0, 1), Vector::new(8, 1)], terrain_cost);
+
+        assert_eq!(goals.get(&Vector::new(4, 1)), Some(4.0));
+        assert_eq!(goals.get(&Vector::new(6, 0)), Some(3.0));
+        assert_eq!(goals.next_step(&Vector::new(6, 1)), Some(Vector::new(7, 1)));
+        assert_eq!(goals.next_step(&Vector::new(8, 1)), None);
+
+        let corridor = Map::empty(9, 1);
+        let threat = corridor.dijkstra(&[Vector::new(2, 0)], terrain_cost);
+        let flee = threat.flee(&corridor, 1.2, terrain_cost);
+
+        assert_eq!(flee.next_step(&Vector::new(4, 0)), Some(Vector::new(5, 0)));
+        assert_eq!(flee.next_step(&Vector::new(1, 0)), Some(Vector::new(0, 0)));
+    }
+
+    proptest! {
+        #[test]
+        fn test_find_path_is_as_cheap_as_dijkstra(walls in prop::collection::vec((0..12i32, 0..12i32), 0..50), water in prop::collection::vec((0..12i32, 0..12i32), 0..30), start in (0..12i32, 0..12i32), goal in (0..12i32, 0..12i32)) {
+            let mut map = Map::empty(12, 12);
+
+            for (x, y) in water {
+                *map.get_mut(&Vector::new(x, y)).unwrap() = Tile::of("shallow_water");
+            }
+
+            for (x, y) in walls {
+                *map.get_mut(&Vector::new(x, y)).unwrap() = Tile::wall();
+            }
+
+            let (start, goal) = (Vector::new(start.0, start.1), Vector::new(goal.0, goal.1));
+            let path = map.find_path(start, goal, min_terrain_cost(), terrain_cost);
+            let expected = map.dijkstra(&[start], terrain_cost).get(&goal);
+
+            match expected {
+                Some(distance) if start != goal => {
+                    prop_assert_eq!(path_cost(&map, &path), distance);
+
+                    let mut previous = start;
+                    for step in path.iter() {
+                        prop_assert_eq!((step.x - previous.x).abs() + (step.y - previous.y).abs(), 1);
+                        previous = *step;
+                    }
+                },
+                _ => prop_assert!(path.is_empty()),
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::ecs::{entity::EntityId, world::World, ECSError};
+
+pub struct Scene {
+    name: String,
+    world: World,
+}
+
+impl Scene {
+    pub fn new(name: String, world: World) -> Self {
+        Scene { name, world }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn into_world(self) -> World {
+        self.world
+    }
+}
+
+/// A stack of worlds, only the top scene is ticked and rendered while the ones below it are kept frozen
+pub struct SceneStack {
+    scenes: Vec<Scene>,
+}
+
+impl SceneStack {
+    pub fn new(root: Scene) -> Self {
+        SceneStack { scenes: vec![root] }
+    }
+
+    pub fn push(&mut self, scene: Scene) {
+        self.scenes.push(scene);
+    }
+
+    /// Pops the top scene, the root scene is never popped
+    pub fn pop(&mut self) -> Option<Scene> {
+        if self.scenes.len() > 1 {
+            self.scenes.pop()
+        } else {
+            None
+        }
+    }
+
+    /// Pops every scene above the root scene
+    pub fn clear(&mut self) {
+        self.scenes.truncate(1);
+    }
+
+    pub fn replace(&mut self, scene: Scene) -> Scene {
+        let top = self.scenes.last_mut().expect("a root scene");
+
+        std::mem::replace(top, scene)
+    }
+
+    pub fn current(&self) -> &Scene {
+        self.scenes.last().expect("a root scene")
+    }
+
+    pub fn current_mut(&mut self) -> &mut Scene {
+        self.scenes.last_mut().expect("a root scene")
+    }
+
+    pub fn world(&self) -> &World {
+        self.current().world()
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        self.current_mut().world_mut()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Scene> {
+        self.scenes.iter().rev().find(|scene| scene.name() == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Scene> {
+        self.scenes.iter_mut().rev().find(|scene| scene.name() == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    fn index_of(&self, name: &str) -> Option<usize> {
+        self.scenes.iter().rposition(|scene| scene.name() == name)
+    }
+
+    /// Moves an entity with all of its components from one scene into another
+    pub fn transfer(&mut self, id: &EntityId, from: &str, to: &str) -> Result<EntityId, ECSError> {
+        let from = self.index_of(from).ok_or(ECSError::CouldNotRetrieve)?;
+        let to = self.index_of(to).ok_or(ECSError::CouldNotRetrieve)?;
+
+        if from == to {
+            return Ok(id.clone());
+        }
+
+        let (low, high) = self.scenes.split_at_mut(from.max(to));
+
+        let (source, destination) = if from < to {
+            (&mut low[from], &mut high[0])
+        } else {
+            (&mut high[0], &mut low[to])
+        };
+
+        source.world_mut().transfer(id, destination.world_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{components::{register_components, Position, WantsToMove}, ecs::{entity::{Entity, EntityId}, world::World}, vectors::{Vector, RIGHT_VECTOR}};
+
+    use super::{Scene, SceneStack};
+
+    /// Whether each entity inserted into the world carried its sparse component when the insert hooks ran
+    struct Seen(Vec<bool>);
+
+    fn record(world: &mut World, id: &EntityId) {
+        let moving = world.has_component::<WantsToMove>(id);
+
+        if let Some(mut seen) = world.get_resource_mut::<Seen>() {
+            seen.0.push(moving);
+        }
+    }
+
+    #[test]
+    fn test_transfer_between_scenes() {
+        let mut root = World::new();
+        register_components(&mut root);
+
+        let id = root.insert(Entity::new().insert_component(Position::new(2, 3, 0)).unwrap().insert_component(WantsToMove::new(RIGHT_VECTOR)).unwrap().build()).unwrap();
+        root.tag(&id, "courier");
+
+        let mut overlay = World::new();
+        overlay.insert_resource(Seen(Vec::new())).unwrap();
+        overlay.add_insert_hook(record);
+
+        let mut scenes = SceneStack::new(Scene::new("root".into(), root));
+        scenes.push(Scene::new("overlay".into(), overlay));
+
+        let moved = scenes.transfer(&id, "root", "overlay").unwrap();
+
+        let overlay = scenes.world();
+        assert_eq!(overlay.get_resource::<Seen>().unwrap().0, vec![true]);
+        assert_eq!(overlay.get_component::<Position>(&moved).unwrap().coords(), Vector::new(2, 3));
+        assert_eq!(overlay.tagged("courier"), std::slice::from_ref(&moved));
+
+        let root = scenes.get("root").unwrap().world();
+        assert!(root.get(&id).is_none());
+        assert!(root.tagged("courier").is_empty());
+
+        assert!(scenes.transfer(&id, "root", "overlay").is_err());
+        assert!(scenes.transfer(&moved, "overlay", "missing").is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use serde::Deserialize;
+
+use crate::{add_system, components, constants, dungeon::{self, Dungeon}, entities, lighting::LightMap, map, mapgen::{Connectivity, GeneratorKind}, spatial::{self, SpatialIndex}, systems, theme::Theme, ecs::{entity::{Entity, EntityId}, world::World, ECSError}};
+
+/// Describes how a new game should be built, read from `raws/setup.json`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct GameSetup {
+    pub seed: Option<u64>,
+    pub player_name: String,
+    pub generator: GeneratorKind,
+    pub connectivity: Connectivity,
+}
+
+impl GameSetup {
+    pub fn new(player_name: String) -> Self {
+        GameSetup { seed: None, player_name, generator: GeneratorKind::default(), connectivity: Connectivity::default() }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_generator(mut self, generator: GeneratorKind) -> Self {
+        self.generator = generator;
+        self
+    }
+
+    /// Clears the world and populates it with a fresh game, a random seed is rolled when none was given
+    pub fn build(&self, world: &mut World) -> Result<EntityId, ECSError> {
+        let seed = self.seed.unwrap_or_else(rand::random);
+
+        let dungeon = Dungeon::new(seed, self.generator, self.connectivity);
+        let (map, layout) = dungeon.generate(0);
+        let spawn = layout.spawn;
+        let prefabs = layout.prefabs.clone();
+
+        world.clear();
+
+        components::register_components(world);
+
+        world
+            .insert_resource(Theme::new())?
+            .insert_resource(map)?
+            .insert_resource(layout)?
+            .insert_resource(dungeon)?
+            .insert_resource(systems::TickInfo::new())?
+            .insert_resource(systems::Timers::new())?
+            .insert_resource(systems::TurnScheduler::new())?
+            .insert_resource(systems::BumpEvents::new())?
+            .insert_resource(systems::TerrainEvents::new())?
+            .insert_resource(LightMap::new(constants::MAP_SIZE.0, constants::MAP_SIZE.1, constants::AMBIENT_LIGHT))?
+            .insert_resource(SpatialIndex::new())?;
+
+        world.add_id_hook(map::sync_blockers)
+            .add_id_hook(spatial::sync_index)
+            .add_insert_hook(map::index_inserted_blocker)
+            .add_insert_hook(spatial::index_inserted);
+
+        add_system!(world, systems::MovementSystem::new(), 1010);
+        add_system!(world, systems::TickSystem::new(), 1000);
+        add_system!(world, systems::InitiativeSystem::new(), 995);
+        add_system!(world, systems::TimerSystem::new(), 997);
+        add_system!(world, systems::LifetimeSystem::new(), 980);
+        add_system!(world, systems::TerrainSystem::new(), -800);
+        add_system!(world, systems::LightingSystem::new(), -850);
+        add_system!(world, systems::ViewSystem::new(), -900);
+        add_system!(world, systems::MemorySystem::new(), -950);
+        add_system!(world, systems::DebugSystem::new(components::DebugLevel::None), -1000);
+
+        let player = world.insert(entities::player(
+            Ok(Entity::new()),
+            self.player_name.clone(),
+            spawn.x,
+            spawn.y
+        )?.build())?;
+
+        world.tag(&player, constants::PLAYER_TAG);
+
+        entities::spawn_prefabs(world, &prefabs)?;
+
+        dungeon::reindex(world);
+
+        Ok(player)
+    }
+}
+
+impl Default for GameSetup {
+    fn default() -> Self {
+        GameSetup::new(constants::DEFAULT_PLAYER_NAME.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{constants, dungeon::Dungeon, ecs::world::World, map::Map, mapgen::MapLayout, systems::TickInfo};
+
+    use super::GameSetup;
+
+    struct Stale;
+
+    #[test]
+    fn test_build_replaces_the_previous_game() {
+        let mut world = World::new();
+        let setup = GameSetup::default().with_seed(5);
+
+        setup.build(&mut world).unwrap();
+
+        let (entities, systems) = (world.iter().count(), world.system_count());
+        let spawn = world.get_resource::<MapLayout>().unwrap().spawn;
+
+        world.insert_resource(Stale).unwrap();
+        world.get_resource_mut::<Map>().unwrap().get_mut(&spawn).unwrap().discover();
+
+        for _ in 0..3 {
+            world.tick();
+            world.apply_deferred();
+        }
+
+        assert!(world.get_resource::<TickInfo>().unwrap().current_tick().is_some());
+
+        let player = setup.build(&mut world).unwrap();
+
+        assert_eq!(world.tagged(constants::PLAYER_TAG), std::slice::from_ref(&player));
+        assert_eq!(world.iter().count(), entities);
+        assert_eq!(world.system_count(), systems);
+        assert!(world.get_resource::<Stale>().is_none());
+        assert!(!world.get_resource::<Map>().unwrap().get(&spawn).unwrap().discovered());
+        assert_eq!(world.get_resource::<MapLayout>().unwrap().spawn, spawn);
+        assert_eq!(world.get_resource::<Dungeon>().unwrap().depth(), 0);
+        assert!(world.get_resource::<TickInfo>().unwrap().current_tick().is_none());
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use crate::{vectors::Vector, ecs::{world::World, entity::EntityId}, components::Position, query};
+
+/// Indexes positioned entities by tile, kept up to date as entities move rather than rebuilt
+#[derive(Default)]
+pub struct SpatialIndex {
+    cells: HashMap<Vector, Vec<EntityId>>,
+    positions: HashMap<EntityId, Vector>,
+}
+
+impl SpatialIndex {
+    pub fn new() -> Self {
+        SpatialIndex { cells: HashMap::new(), positions: HashMap::new() }
+    }
+
+    /// Indexes every positioned entity in the world, replacing the current contents
+    pub fn index(&mut self, world: &World) {
+        self.clear();
+
+        for entity in world.query_entities(&query!(Position)) {
+            if let (Some(position), Some(id)) = (entity.get_component::<Position>(), entity.id()) {
+                self.insert(id, position.coords());
+            }
+        }
+    }
+
+    /// Inserts the entity at the position, moving it if it was already indexed
+    pub fn insert(&mut self, id: &EntityId, position: Vector) {
+        if let Some(old) = self.positions.get(id) {
+            if *old == position {
+                return;
+            }
+
+            self.remove(id);
+        }
+
+        self.cells.entry(position).or_default().push(id.clone());
+        self.positions.insert(id.clone(), position);
+    }
+
+    pub fn move_to(&mut self, id: &EntityId, position: Vector) {
+        self.insert(id, position);
+    }
+
+    pub fn remove(&mut self, id: &EntityId) -> Option<Vector> {
+        let position = self.positions.remove(id)?;
+
+        if let Some(ids) = self.cells.get_mut(&position) {
+            ids.retain(|other| other != id);
+
+            if ids.is_empty() {
+                self.cells.remove(&position);
+            }
+        }
+
+        Some(position)
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.positions.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn position(&self, id: &EntityId) -> Option<Vector> {
+        self.positions.get(id).copied()
+    }
+
+    pub fn contains(&self, id: &EntityId) -> bool {
+        self.positions.contains_key(id)
+    }
+
+    pub fn at(&self, position: &Vector) -> &[EntityId] {
+        match self.cells.get(position) {
+            Some(ids) => ids,
+            None => &[],
+        }
+    }
+
+    /// Every entity with min <= position <= max on both axes
+    pub fn within_rect(&self, min: Vector, max: Vector) -> Vec<(Vector, &EntityId)> {
+        let area = (max.x - min.x + 1).max(0) as usize * (max.y - min.y + 1).max(0) as usize;
+
+        let mut found = Vec::new();
+
+        if area > self.cells.len() {
+            for (position, ids) in self.cells.iter() {
+                if min.x <= position.x && position.x <= max.x && min.y <= position.y && position.y <= max.y {
+                    found.extend(ids.iter().map(|id| (*position, id)));
+                }
+            }
+        } else {
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    let position = Vector::new(x, y);
+
+                    found.extend(self.at(&position).iter().map(|id| (position, id)));
+                }
+            }
+        }
+
+        found
+    }
+
+    pub fn within_radius(&self, center: Vector, radius: f32) -> Vec<(Vector, &EntityId)> {
+        let extent = radius.ceil() as i32;
+
+        self.within_rect(center - Vector::new(extent, extent), center + Vector::new(extent, extent))
+            .into_iter()
+            .filter(|(position, _)| Vector::distance(&center, position) <= radius)
+            .collect()
+    }
+
+    /// The k closest entities to the center, searching outwards ring by ring
+    pub fn nearest(&self, center: Vector, k: usize) -> Vec<(Vector, &EntityId)> {
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let mut found: Vec<(f32, Vector, &EntityId)> = Vec::new();
+        let mut radius = 0;
+
+        loop {
+            let side = (2 * radius + 1) as usize;
+
+            // Once the ring covers more tiles than there are occupied cells scanning every cell is cheaper
+            if side * side > self.cells.len() * 4 {
+                found = self.cells.iter()
+                    .flat_map(|(position, ids)| ids.iter().map(|id| (Vector::distance(&center, position), *position, id)))
+                    .collect();
+
+                found.sort_by(|a, b| a.0.total_cmp(&b.0));
+                found.truncate(k);
+
+                return found.into_iter().map(|(_, position, id)| (position, id)).collect();
+            }
+
+            for position in ring(center, radius) {
+                found.extend(self.at(&position).iter().map(|id| (Vector::distance(&center, &position), position, id)));
+            }
+
+            if found.len() >= k {
+                found.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                // Anything not yet found lies further out than the current ring
+                if found[k - 1].0 <= radius as f32 {
+                    found.truncate(k);
+
+                    return found.into_iter().map(|(_, position, id)| (position, id)).collect();
+                }
+            }
+
+            radius += 1;
+        }
+    }
+}
+
+fn ring(center: Vector, radius: i32) -> Vec<Vector> {
+    if radius == 0 {
+        return vec![center];
+    }
+
+    let mut points = Vec::with_capacity(8 * radius as usize);
+
+    for offset in -radius..=radius {
+        points.push(center + Vector::new(offset, -radius));
+        points.push(center + Vector::new(offset, radius));
+    }
+
+    for offset in (-radius + 1)..radius {
+        points.push(center + Vector::new(-radius, offset));
+        points.push(center + Vector::new(radius, offset));
+    }
+
+    points
+}
+
+/// Keeps the spatial index in sync with despawned entities and entities which changed archetype, including gaining or losing a position
+pub fn sync_index(world: &mut World, old: &EntityId, new: Option<&EntityId>) {
+    if let Some(mut index) = world.get_resource_mut::<SpatialIndex>() {
+        index.remove(old);
+
+        if let Some(new) = new {
+            if let Some(position) = world.get_component::<Position>(new) {
+                index.insert(new, position.coords());
+            }
+        }
+    }
+}
+
+/// Indexes entities as they are spawned
+pub fn index_inserted(world: &mut World, id: &EntityId) {
+    if let (Some(mut index), Some(position)) = (world.get_resource_mut::<SpatialIndex>(), world.get_component::<Position>(id)) {
+        index.insert(id, position.coords());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{components::{BlocksTile, Position}, ecs::{archetype::Archetype, entity::{Entity, EntityId}, world::World}, vectors::Vector};
+
+    use super::{index_inserted, sync_index, SpatialIndex};
+
+    fn id(index: usize) -> EntityId {
+        EntityId::new(Archetype::new(), index)
+    }
+
+    #[test]
+    fn test_spatial_index_queries() {
+        let mut index = SpatialIndex::new();
+
+        for i in 0..10 {
+            index.insert(&id(i as usize), Vector::new(i * 2, i));
+        }
+
+        assert_eq!(index.at(&Vector::new(4, 2)), &[id(2)]);
+        assert_eq!(index.within_rect(Vector::new(0, 0), Vector::new(6, 6)).len(), 4);
+        assert_eq!(index.within_radius(Vector::new(0, 0), 4.5).len(), 3);
+
+        let nearest: Vec<EntityId> = index.nearest(Vector::new(9, 5), 2).into_iter().map(|(_, id)| id.clone()).collect();
+        assert_eq!(nearest, vec![id(5), id(4)]);
+
+        index.move_to(&id(5), Vector::new(40, 40));
+        index.remove(&id(4));
+
+        assert!(index.at(&Vector::new(10, 5)).is_empty());
+        assert_eq!(index.nearest(Vector::new(9, 5), 1)[0].1, &id(6));
+        assert_eq!(index.len(), 9);
+    }
+
+    #[test]
+    fn test_hooks_keep_the_index_in_sync() {
+        let mut world = World::new();
+        world.insert_resource(SpatialIndex::new()).unwrap();
+        world.add_id_hook(sync_index).add_insert_hook(index_inserted);
+
+        let spawned = world.insert(Entity::new().insert_component(Position::new(3, 4, 0)).unwrap().build()).unwrap();
+        let unplaced = world.insert(Entity::new().insert_component(BlocksTile::new()).unwrap().build()).unwrap();
+
+        assert_eq!(world.get_resource::<SpatialIndex>().unwrap().at(&Vector::new(3, 4)), std::slice::from_ref(&spawned));
+        assert_eq!(world.get_resource::<SpatialIndex>().unwrap().len(), 1);
+
+        let spawned = world.add_component(&spawned, BlocksTile::new()).unwrap();
+        let placed = world.add_component(&unplaced, Position::new(5, 5, 0)).unwrap();
+
+        assert_eq!(world.get_resource::<SpatialIndex>().unwrap().at(&Vector::new(3, 4)), std::slice::from_ref(&spawned));
+        assert_eq!(world.get_resource::<SpatialIndex>().unwrap().at(&Vector::new(5, 5)), std::slice::from_ref(&placed));
+
+        let placed = world.remove_component::<Position>(&placed).unwrap();
+        world.despawn(&spawned);
+
+        assert!(world.get_resource::<SpatialIndex>().unwrap().is_empty());
+        assert!(world.get(&placed).is_some());
+    }
+}
@@ -1,6 +1,13 @@
+use serde::{Deserialize, Serialize};
+
 use crate::components::*;
-use crate::ecs::{query::Query, system::System, world::World};
-use crate::map::Map;
+use crate::constants::ACTION_COST;
+use crate::ecs::{entity::EntityId, query::Query, system::{System, RunCriteria}, world::World};
+use crate::lighting::LightMap;
+use crate::map::{Map, TerrainChange};
+use crate::spatial::SpatialIndex;
+use crate::tiles::{tile_types, TileFlag};
+use crate::vectors::Vector;
 
 pub struct DebugSystem {
     pub min_level: DebugLevel,
@@ -19,7 +26,7 @@ impl DebugSystem {
 impl System for DebugSystem {
     fn execute(&self, world: &World) {
         for e in world.query_entities(&Query::new().include::<Debug>()) {
-            if let Some(mut debug) = e.get_component_mut::<Debug>() {
+            if let Some(mut debug) = e.id().and_then(|id| world.get_component_mut::<Debug>(id)) {
                 let name: String = match e.get_component::<Named>() {
                     Some(named) => named.name.to_string(),
                     None => format!("Entity({:?})", e.id()),
@@ -48,10 +55,16 @@ impl ViewSystem {
 }
 
 impl System for ViewSystem {
+    fn run_criteria(&self) -> RunCriteria {
+        RunCriteria::If(behaviour_tick)
+    }
+
     fn execute(&self, world: &World) {
         let query = Query::new().include::<Viewshed>().include::<Position>();
 
         if let Some(mut map) = world.get_resource_mut::<Map>() {
+            let lights = world.get_resource::<LightMap>();
+
             for entity in world.query_entities(&query) {
                 if let (Some(mut viewshed), Some(position)) = (
                     entity.get_component_mut::<Viewshed>(),
@@ -63,17 +76,273 @@ impl System for ViewSystem {
                             tick_info.update_last_view_update();   
                         }
                      
-                        (*viewshed).update(&mut *map, position.coords(), is_player, tick_info.last_view_update_tick());
+                        (*viewshed).update(&mut *map, position.coords(), lights.as_deref(), is_player, tick_info.last_view_update_tick());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Recomputes the `LightMap` every behaviour tick, viewsheds are invalidated whenever the light changed as darkness hides tiles
+pub struct LightingSystem {}
+
+impl LightingSystem {
+    pub fn new() -> Self {
+        LightingSystem {}
+    }
+}
+
+impl System for LightingSystem {
+    fn run_criteria(&self) -> RunCriteria {
+        RunCriteria::If(behaviour_tick)
+    }
+
+    fn execute(&self, world: &World) {
+        let (Some(map), Some(mut lights)) = (world.get_resource::<Map>(), world.get_resource_mut::<LightMap>()) else {
+            return;
+        };
+
+        let query = Query::new().include::<LightSource>().include::<Position>();
+
+        let sources: Vec<(Vector, LightSource)> = world.query_entities(&query)
+            .filter_map(|entity| Some((entity.get_component::<Position>()?.coords(), *entity.get_component::<LightSource>()?)))
+            .collect();
+
+        let computed = LightMap::compute(&map, lights.ambient(), sources.iter().map(|(position, source)| (*position, source)));
+
+        if computed == *lights {
+            return;
+        }
+
+        *lights = computed;
+
+        for entity in world.query_entities(&Query::new().include::<Viewshed>()) {
+            if let Some(mut viewshed) = entity.get_component_mut::<Viewshed>() {
+                viewshed.mark_dirty();
+            }
+        }
+    }
+}
+
+/// Remembers the glyph of the top entity on every tile the player sees, tiles out of view keep showing what was last seen on them
+pub struct MemorySystem {}
+
+impl MemorySystem {
+    pub fn new() -> Self {
+        MemorySystem {}
+    }
+}
+
+impl System for MemorySystem {
+    fn run_criteria(&self) -> RunCriteria {
+        RunCriteria::If(behaviour_tick)
+    }
+
+    fn execute(&self, world: &World) {
+        let query = Query::new().include::<Player>().include::<Viewshed>();
+
+        let (Some(player), Some(mut map), Some(index)) = (
+            world.query_one_entity(&query),
+            world.get_resource_mut::<Map>(),
+            world.get_resource::<SpatialIndex>(),
+        ) else {
+            return;
+        };
+
+        let Some(viewshed) = player.get_component::<Viewshed>() else {
+            return;
+        };
+
+        for position in viewshed.iter() {
+            // The player is never remembered, they are always where they are seen
+            let glyph = index.at(position).iter()
+                .filter(|id| !world.has_component::<Player>(id))
+                .filter_map(|id| Some((world.get_component::<Position>(id)?.priority(), world.get_component::<Renderer>(id)?.glyph())))
+                .max_by_key(|(priority, _)| *priority)
+                .map(|(_, glyph)| glyph);
+
+            map.remember(position, glyph);
+        }
+    }
+}
+
+/// An entity tried to move onto a tile occupied by `blocker`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Bump {
+    pub entity: EntityId,
+    pub blocker: EntityId,
+}
+
+/// The bumps caused by movement during the current frame, a combat system can turn these into attacks
+#[derive(Default)]
+pub struct BumpEvents {
+    bumps: Vec<Bump>,
+}
+
+impl BumpEvents {
+    pub fn new() -> Self {
+        BumpEvents { bumps: Vec::new() }
+    }
+
+    pub fn push(&mut self, entity: EntityId, blocker: EntityId) {
+        self.bumps.push(Bump { entity, blocker });
+    }
+
+    pub fn clear(&mut self) {
+        self.bumps.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Bump> {
+        self.bumps.iter()
+    }
+}
+
+/// The terrain changes made during the current frame
+#[derive(Default)]
+pub struct TerrainEvents {
+    changes: Vec<TerrainChange>,
+}
+
+impl TerrainEvents {
+    pub fn new() -> Self {
+        TerrainEvents { changes: Vec::new() }
+    }
+
+    pub fn clear(&mut self) {
+        self.changes.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TerrainChange> {
+        self.changes.iter()
+    }
+}
+
+/// Publishes the map's terrain changes as `TerrainEvents` and invalidates every viewshed in range of a tile whose opaqueness changed
+pub struct TerrainSystem {}
+
+impl TerrainSystem {
+    pub fn new() -> Self {
+        TerrainSystem {}
+    }
+}
+
+impl System for TerrainSystem {
+    fn execute(&self, world: &World) {
+        let (Some(mut map), Some(mut events)) = (world.get_resource_mut::<Map>(), world.get_resource_mut::<TerrainEvents>()) else {
+            return;
+        };
+
+        events.changes = map.take_changes();
+
+        // Only changes in opaqueness can change what is visible
+        let tile_types = tile_types();
+        let changed: Vec<Vector> = events.iter()
+            .filter(|change| tile_types.get(change.from).opaqueness != tile_types.get(change.into).opaqueness)
+            .map(|change| change.position)
+            .collect();
+
+        if changed.is_empty() {
+            return;
+        }
+
+        let query = Query::new().include::<Viewshed>().include::<Position>();
+
+        for entity in world.query_entities(&query) {
+            if let (Some(mut viewshed), Some(position)) = (entity.get_component_mut::<Viewshed>(), entity.get_component::<Position>()) {
+                if changed.iter().any(|change| Vector::distance(&position.coords(), change) <= viewshed.view_distance) {
+                    viewshed.mark_dirty();
+                }
+            }
+        }
+    }
+}
+
+pub struct MovementSystem {}
+
+impl MovementSystem {
+    pub fn new() -> Self {
+        MovementSystem {}
+    }
+}
+
+impl System for MovementSystem {
+    fn execute(&self, world: &World) {
+        let query = Query::new().include::<WantsToMove>().include::<Position>();
+
+        if let Some(mut map) = world.get_resource_mut::<Map>() {
+            let mut bumps = world.get_resource_mut::<BumpEvents>();
+
+            if let Some(bumps) = bumps.as_mut() {
+                bumps.clear();
+            }
+
+            for entity in world.query_entities(&query) {
+                let id = match entity.id() {
+                    Some(id) => id.clone(),
+                    None => continue,
+                };
+
+                if let (Some(intent), Some(mut position)) = (world.get_component::<WantsToMove>(&id), entity.get_component_mut::<Position>()) {
+                    let target = position.coords() + intent.delta;
+                    let result = position.try_move(&map, intent.delta);
+
+                    if let (MoveResult::BlockedBy(blocker), Some(bumps)) = (&result, bumps.as_mut()) {
+                        bumps.push(id.clone(), blocker.clone());
+                    }
+
+                    // Walking into a closed door or a lever uses it instead, which takes the move
+                    let used = result == MoveResult::Blocked
+                        && map.get(&target).is_some_and(|tile| tile.tile_type().has_flag(TileFlag::UseOnBump))
+                        && map.use_tile(&target);
+
+                    if result.moved() {
+                        if entity.has_component::<BlocksTile>() {
+                            map.set_blocker(&position.coords(), &id);
+                        }
+
+                        if let Some(mut index) = world.get_resource_mut::<SpatialIndex>() {
+                            index.move_to(&id, position.coords());
+                        }
+
+                        if let Some(mut viewshed) = entity.get_component_mut::<Viewshed>() {
+                            viewshed.mark_dirty();
+                        }
+                    }
+
+                    if used || result.moved() {
+                        // Stepping onto slow terrain such as water costs more, using a tile costs a plain action
+                        let cost = match result.moved() {
+                            true => map.get(&position.coords()).map_or(1.0, |tile| tile.movement_cost()),
+                            false => 1.0,
+                        };
+
+                        if let Some(mut actor) = entity.get_component_mut::<Actor>() {
+                            actor.spend((ACTION_COST as f32 * cost) as i32);
+                        }
+
+                        if entity.has_component::<Player>() {
+                            if let (Some(mut scheduler), Some(mut tick_info)) = (world.get_resource_mut::<TurnScheduler>(), world.get_resource_mut::<TickInfo>()) {
+                                scheduler.end_player_turn(&mut tick_info);
+                            }
+                        }
                     }
                 }
+
+                world.defer(move |world| { let _ = world.remove_component::<WantsToMove>(&id); });
             }
         }
     }
 }
 
+/// Separates render frames from game turns
+/// Every world tick is a frame, a frame is also a behaviour tick when a turn was requested, usually by the player acting
+/// `current_tick` counts behaviour ticks and so is the game's turn counter
 pub struct TickInfo {
     current_tick: Option<usize>,
+    frame: usize,
     behaviour_tick: bool,
+    behaviour_requested: bool,
     last_view_update_tick: Option<usize>
 }
 
@@ -81,7 +350,10 @@ impl TickInfo {
     pub fn new() -> Self {
         TickInfo {
             current_tick: None,
+            frame: 0,
             behaviour_tick: false,
+            // The first frame is a behaviour tick so the world is simulated once before the player acts
+            behaviour_requested: true,
             last_view_update_tick: None
         }
     }
@@ -90,19 +362,35 @@ impl TickInfo {
         self.current_tick
     }
 
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Whether the current frame advances the game by a turn
     pub fn behaviour_tick(&self) -> bool {
-        todo!()
+        self.behaviour_tick
+    }
+
+    /// Makes the next frame a behaviour tick
+    pub fn request_behaviour_tick(&mut self) {
+        self.behaviour_requested = true;
     }
 
     pub fn offset_tick(&self, offset: i32) -> Option<usize> {
         if offset >= 0 {
             Some(self.current_tick? + offset as usize)
         } else {
-            if -offset as usize > self.current_tick? {
-                None
-            } else {
-                Some(self.current_tick? + offset as usize)
-            }
+            self.current_tick?.checked_sub(offset.unsigned_abs() as usize)
+        }
+    }
+
+    fn begin_frame(&mut self) {
+        self.frame += 1;
+        self.behaviour_tick = self.behaviour_requested;
+        self.behaviour_requested = false;
+
+        if self.behaviour_tick {
+            self.increment_tick();
         }
     }
 
@@ -123,6 +411,11 @@ impl TickInfo {
     }
 }
 
+/// Run criteria for systems which should only run once per game turn
+pub fn behaviour_tick(world: &World) -> bool {
+    world.get_resource::<TickInfo>().is_some_and(|tick_info| tick_info.behaviour_tick())
+}
+
 pub struct TickSystem {}
 
 impl TickSystem {
@@ -134,7 +427,609 @@ impl TickSystem {
 impl System for TickSystem {
     fn execute(&self, world: &World) {
         if let Some(mut tick_info) = world.get_resource_mut::<TickInfo>() {
-            tick_info.increment_tick();
+            tick_info.begin_frame();
         }
     } 
 }
+
+/// Tracks whose turn it is, the simulation is paused while it is the player's turn
+pub struct TurnScheduler {
+    acting: Vec<EntityId>,
+    player_turn: bool,
+}
+
+impl TurnScheduler {
+    pub fn new() -> Self {
+        TurnScheduler { acting: Vec::new(), player_turn: false }
+    }
+
+    pub fn player_turn(&self) -> bool {
+        self.player_turn
+    }
+
+    /// Ends the player's turn, the simulation resumes on the next frame
+    pub fn end_player_turn(&mut self, tick_info: &mut TickInfo) {
+        self.player_turn = false;
+        tick_info.request_behaviour_tick();
+    }
+
+    /// The non player entities which may act during the current game tick
+    pub fn acting(&self) -> &[EntityId] {
+        &self.acting
+    }
+
+    pub fn is_acting(&self, id: &EntityId) -> bool {
+        self.acting.contains(id)
+    }
+
+    /// Forgets the entities given a turn, their ids stop being valid once they leave the world
+    pub fn clear_acting(&mut self) {
+        self.acting.clear();
+    }
+}
+
+pub struct InitiativeSystem {}
+
+impl InitiativeSystem {
+    pub fn new() -> Self {
+        InitiativeSystem {}
+    }
+}
+
+impl System for InitiativeSystem {
+    fn run_criteria(&self) -> RunCriteria {
+        RunCriteria::If(behaviour_tick)
+    }
+
+    fn execute(&self, world: &World) {
+        if let Some(mut scheduler) = world.get_resource_mut::<TurnScheduler>() {
+            // Actors which were given a turn but did nothing with it wait
+            for id in std::mem::take(&mut scheduler.acting) {
+                if let Some(mut actor) = world.get_component_mut::<Actor>(&id) {
+                    if actor.ready() {
+                        actor.spend(ACTION_COST);
+                    }
+                }
+            }
+
+            for entity in world.query_entities(&Query::new().include::<Actor>()) {
+                if let (Some(mut actor), Some(id)) = (entity.get_component_mut::<Actor>(), entity.id()) {
+                    actor.gain();
+
+                    if actor.ready() {
+                        if entity.has_component::<Player>() {
+                            scheduler.player_turn = true;
+                        } else {
+                            scheduler.acting.push(id.clone());
+                        }
+                    }
+                }
+            }
+
+            if !scheduler.player_turn {
+                if let Some(mut tick_info) = world.get_resource_mut::<TickInfo>() {
+                    tick_info.request_behaviour_tick();
+                }
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct TimerId(u64);
+
+/// What happens when a timer comes due
+/// Event:         Fires a named event which other systems can read from `Timers::fired` during the same tick
+/// DespawnTagged: Despawns every entity carrying the tag
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum TimerAction {
+    Event(String),
+    DespawnTagged(String),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Timer {
+    id: TimerId,
+    due: usize,
+    period: Option<usize>,
+    action: TimerAction,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Timers {
+    timers: Vec<Timer>,
+    next_id: u64,
+    fired: Vec<String>,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Timers { timers: Vec::new(), next_id: 0, fired: Vec::new() }
+    }
+
+    fn schedule(&mut self, due: usize, period: Option<usize>, action: TimerAction) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+
+        self.timers.push(Timer { id, due, period, action });
+
+        id
+    }
+
+    /// Runs the action once at the given tick
+    pub fn at(&mut self, tick: usize, action: TimerAction) -> TimerId {
+        self.schedule(tick, None, action)
+    }
+
+    /// Runs the action every `period` ticks, starting at the given tick
+    pub fn every(&mut self, start: usize, period: usize, action: TimerAction) -> TimerId {
+        self.schedule(start, Some(period.max(1)), action)
+    }
+
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        let count = self.timers.len();
+
+        self.timers.retain(|timer| timer.id != id);
+
+        self.timers.len() != count
+    }
+
+    pub fn pending(&self) -> usize {
+        self.timers.len()
+    }
+
+    /// Removes and returns the actions due on or before the tick, repeating timers are rescheduled
+    pub fn take_due(&mut self, tick: usize) -> Vec<TimerAction> {
+        let mut due = Vec::new();
+
+        for timer in self.timers.iter_mut() {
+            if timer.due <= tick {
+                due.push(timer.action.clone());
+
+                if let Some(period) = timer.period {
+                    while timer.due <= tick {
+                        timer.due += period;
+                    }
+                }
+            }
+        }
+
+        self.timers.retain(|timer| timer.due > tick);
+
+        due
+    }
+
+    pub fn fired(&self) -> &[String] {
+        &self.fired
+    }
+
+    pub fn has_fired(&self, event: &str) -> bool {
+        self.fired.iter().any(|fired| fired == event)
+    }
+}
+
+pub struct TimerSystem {}
+
+impl TimerSystem {
+    pub fn new() -> Self {
+        TimerSystem {}
+    }
+}
+
+impl System for TimerSystem {
+    fn run_criteria(&self) -> RunCriteria {
+        RunCriteria::If(behaviour_tick)
+    }
+
+    fn execute(&self, world: &World) {
+        let tick = match world.get_resource::<TickInfo>().and_then(|tick_info| tick_info.current_tick()) {
+            Some(tick) => tick,
+            None => return,
+        };
+
+        if let Some(mut timers) = world.get_resource_mut::<Timers>() {
+            timers.fired.clear();
+
+            for action in timers.take_due(tick) {
+                match action {
+                    TimerAction::Event(event) => timers.fired.push(event),
+                    TimerAction::DespawnTagged(tag) => world.defer(move |world| {
+                        for id in world.tagged(tag.as_str()).to_vec() {
+                            world.despawn(&id);
+                        }
+                    }),
+                }
+            }
+        }
+    }
+}
+
+pub struct LifetimeSystem {}
+
+impl LifetimeSystem {
+    pub fn new() -> Self {
+        LifetimeSystem {}
+    }
+}
+
+impl System for LifetimeSystem {
+    fn run_criteria(&self) -> RunCriteria {
+        RunCriteria::If(behaviour_tick)
+    }
+
+    fn execute(&self, world: &World) {
+        for entity in world.query_entities(&Query::new().include::<Lifetime>()) {
+            if let (Some(mut lifetime), Some(id)) = (entity.get_component_mut::<Lifetime>(), entity.id()) {
+                if lifetime.tick() {
+                    let id = id.clone();
+                    world.defer(move |world| { world.despawn(&id); });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{add_system, components::{register_components, Actor, Lifetime, LightSource, Player, Position, Renderer, Viewshed, WantsToMove, BlocksTile}, ecs::{entity::Entity, system::System, world::World}, lighting::LightMap, map::{self, Map, Tile, TileDamageResult}, spatial::SpatialIndex, tiles, vectors::{Vector, RIGHT_VECTOR, UP_VECTOR}};
+
+    use super::{Timers, TimerAction, TimerSystem, LifetimeSystem, TickInfo, TickSystem, InitiativeSystem, TurnScheduler, MovementSystem, BumpEvents, TerrainSystem, TerrainEvents, LightingSystem, ViewSystem, MemorySystem};
+
+    #[test]
+    fn test_movement_blocked_by_entity() {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        world.insert_resource(Map::empty(5, 5)).unwrap().insert_resource(BumpEvents::new()).unwrap();
+        world.add_id_hook(map::sync_blockers);
+
+        add_system!(world, MovementSystem::new(), 1010);
+
+        let mover = world.insert(Entity::new().insert_component(Position::new(2, 2, 0)).unwrap().insert_component(BlocksTile::new()).unwrap().build()).unwrap();
+        let blocker = world.insert(Entity::new().insert_component(Position::new(3, 2, 0)).unwrap().insert_component(BlocksTile::new()).unwrap().build()).unwrap();
+
+        world.get_resource_mut::<Map>().unwrap().index_blockers(&world);
+
+        world.add_component(&mover, WantsToMove::new(RIGHT_VECTOR)).unwrap();
+        world.tick();
+        world.apply_deferred();
+
+        assert_eq!(world.get_component::<Position>(&mover).unwrap().coords(), Vector::new(2, 2));
+        assert_eq!(world.get_resource::<BumpEvents>().unwrap().iter().next().unwrap().blocker, blocker);
+
+        world.despawn(&blocker);
+        assert!(world.get_resource::<Map>().unwrap().blocker(&Vector::new(3, 2)).is_none());
+
+        world.add_component(&mover, WantsToMove::new(RIGHT_VECTOR)).unwrap();
+        world.tick();
+        world.apply_deferred();
+
+        assert_eq!(world.get_resource::<Map>().unwrap().blocker(&Vector::new(3, 2)), Some(&mover));
+        assert!(world.get_resource::<Map>().unwrap().blocker(&Vector::new(2, 2)).is_none());
+    }
+
+    #[test]
+    fn test_movement_resolves_intents() {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        let mut map = Map::empty(5, 5);
+        *map.get_mut(&Vector::new(2, 1)).unwrap() = Tile::wall();
+
+        world.insert_resource(map).unwrap();
+
+        add_system!(world, MovementSystem::new(), 1010);
+
+        let id = world.insert(Entity::new().insert_component(Position::new(2, 2, 0)).unwrap().build()).unwrap();
+
+        world.add_component(&id, WantsToMove::new(UP_VECTOR)).unwrap();
+        world.tick();
+        world.apply_deferred();
+
+        assert_eq!(world.get_component::<Position>(&id).unwrap().coords(), Vector::new(2, 2));
+        assert!(!world.has_component::<WantsToMove>(&id));
+
+        world.add_component(&id, WantsToMove::new(RIGHT_VECTOR)).unwrap();
+        world.tick();
+        world.apply_deferred();
+
+        assert_eq!(world.get_component::<Position>(&id).unwrap().coords(), Vector::new(3, 2));
+    }
+
+    #[test]
+    fn test_initiative_blocks_on_player() {
+        let mut world = World::new();
+
+        world.insert_resource(TickInfo::new()).unwrap().insert_resource(TurnScheduler::new()).unwrap();
+
+        add_system!(world, TickSystem::new(), 1000);
+        add_system!(world, InitiativeSystem::new(), 995);
+
+        let player = world.insert(Entity::new().insert_component(Actor::new(50)).unwrap().insert_component(Player::new()).unwrap().build()).unwrap();
+        let monster = world.insert(Entity::new().insert_component(Actor::new(100)).unwrap().build()).unwrap();
+
+        world.tick();
+        assert!(world.get_resource::<TurnScheduler>().unwrap().is_acting(&monster));
+        assert!(!world.get_resource::<TurnScheduler>().unwrap().player_turn());
+
+        world.tick();
+        assert!(world.get_resource::<TurnScheduler>().unwrap().player_turn());
+
+        // The simulation is paused until the player acts
+        world.tick();
+        world.tick();
+        assert_eq!(world.get_resource::<TickInfo>().unwrap().current_tick(), Some(1));
+        assert_eq!(world.get_component::<Actor>(&player).unwrap().energy, 100);
+    }
+
+    #[test]
+    fn test_tick_info_behaviour_ticks() {
+        let mut tick_info = TickInfo::new();
+
+        tick_info.begin_frame();
+        assert!(tick_info.behaviour_tick());
+        assert_eq!(tick_info.current_tick(), Some(0));
+
+        tick_info.begin_frame();
+        tick_info.begin_frame();
+        assert!(!tick_info.behaviour_tick());
+        assert_eq!(tick_info.current_tick(), Some(0));
+        assert_eq!(tick_info.frame(), 3);
+
+        tick_info.request_behaviour_tick();
+        tick_info.begin_frame();
+        assert!(tick_info.behaviour_tick());
+        assert_eq!(tick_info.current_tick(), Some(1));
+        assert_eq!(tick_info.offset_tick(-1), Some(0));
+        assert_eq!(tick_info.offset_tick(-2), None);
+    }
+
+    #[test]
+    fn test_timers_take_due() {
+        let mut timers = Timers::new();
+
+        timers.at(3, TimerAction::Event("once".into()));
+        let repeating = timers.every(2, 2, TimerAction::Event("repeat".into()));
+
+        assert!(timers.take_due(1).is_empty());
+        assert_eq!(timers.take_due(2), vec![TimerAction::Event("repeat".into())]);
+        assert_eq!(timers.take_due(3), vec![TimerAction::Event("once".into())]);
+        assert_eq!(timers.take_due(4), vec![TimerAction::Event("repeat".into())]);
+        assert_eq!(timers.pending(), 1);
+
+        assert!(timers.cancel(repeating));
+        assert!(timers.take_due(6).is_empty());
+    }
+
+    #[test]
+    fn test_timers_and_lifetimes_despawn() {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        let mut timers = Timers::new();
+        timers.at(0, TimerAction::Event("start".into()));
+        timers.at(1, TimerAction::DespawnTagged("smoke".into()));
+
+        world.insert_resource(TickInfo::new()).unwrap().insert_resource(timers).unwrap();
+
+        let smoke = world.insert(Entity::new().insert_component(Position::new(1, 1, 0)).unwrap().build()).unwrap();
+        world.tag(&smoke, "smoke");
+
+        let fading = world.insert(Entity::new().insert_component(Lifetime::new(2)).unwrap().build()).unwrap();
+
+        let step = |world: &mut World| {
+            world.get_resource_mut::<TickInfo>().unwrap().begin_frame();
+            TimerSystem::new().execute(world);
+            LifetimeSystem::new().execute(world);
+            world.apply_deferred();
+        };
+
+        step(&mut world);
+        assert!(world.get_resource::<Timers>().unwrap().has_fired("start"));
+        assert!(world.get(&smoke).is_some());
+        assert_eq!(world.get_component::<Lifetime>(&fading).unwrap().remaining(), 1);
+
+        world.get_resource_mut::<TickInfo>().unwrap().request_behaviour_tick();
+        step(&mut world);
+        assert!(world.get_resource::<Timers>().unwrap().fired().is_empty());
+        assert!(world.get(&smoke).is_none());
+        assert!(world.get(&fading).is_none());
+        assert_eq!(world.get_resource::<Timers>().unwrap().pending(), 0);
+    }
+
+    #[test]
+    fn test_timers_and_lifetimes_round_trip() {
+        let mut timers = Timers::new();
+        timers.at(3, TimerAction::DespawnTagged("smoke".into()));
+        timers.every(2, 2, TimerAction::Event("repeat".into()));
+
+        let mut loaded: Timers = serde_json::from_str(&serde_json::to_string(&timers).unwrap()).unwrap();
+
+        assert_eq!(loaded.pending(), 2);
+        for tick in 0..6 {
+            assert_eq!(loaded.take_due(tick), timers.take_due(tick));
+        }
+
+        let lifetime = Lifetime::new(7);
+        assert_eq!(serde_json::from_str::<Lifetime>(&serde_json::to_string(&lifetime).unwrap()).unwrap(), lifetime);
+    }
+
+    #[test]
+    fn test_broken_terrain_invalidates_viewsheds() {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        let mut map = Map::empty(30, 5);
+        *map.get_mut(&Vector::new(3, 2)).unwrap() = Tile::wall();
+
+        world.insert_resource(map).unwrap().insert_resource(TerrainEvents::new()).unwrap();
+
+        add_system!(world, TerrainSystem::new(), -800);
+
+        let near = world.insert(Entity::new().insert_component(Position::new(1, 2, 0)).unwrap().insert_component(Viewshed::new(5.0)).unwrap().build()).unwrap();
+        let far = world.insert(Entity::new().insert_component(Position::new(25, 2, 0)).unwrap().insert_component(Viewshed::new(5.0)).unwrap().build()).unwrap();
+
+        for id in [&near, &far] {
+            world.get_component_mut::<Viewshed>(id).unwrap().update(&mut world.get_resource_mut::<Map>().unwrap(), Vector::new(0, 0), None, false, None);
+        }
+
+        let result = world.get_resource_mut::<Map>().unwrap().damage_tile(&Vector::new(3, 2), 200);
+        assert_eq!(result, TileDamageResult::Damaged { remaining: 55 });
+
+        world.tick();
+        assert!(world.get_resource::<TerrainEvents>().unwrap().iter().next().is_none());
+
+        let result = world.get_resource_mut::<Map>().unwrap().damage_tile(&Vector::new(3, 2), 200);
+        assert!(matches!(result, TileDamageResult::Broken { .. }));
+
+        let map = world.get_resource::<Map>().unwrap();
+        let tile = map.get(&Vector::new(3, 2)).unwrap();
+        assert!(tile.walkable() && tile.tile_type().name == tiles::RUBBLE);
+        drop(map);
+
+        world.tick();
+        assert_eq!(world.get_resource::<TerrainEvents>().unwrap().iter().count(), 1);
+        assert!(world.get_component::<Viewshed>(&near).unwrap().dirty());
+        assert!(!world.get_component::<Viewshed>(&far).unwrap().dirty());
+        assert_eq!(world.get_resource_mut::<Map>().unwrap().damage_tile(&Vector::new(3, 2), 1), TileDamageResult::AlreadyBroken);
+        assert_eq!(world.get_resource_mut::<Map>().unwrap().damage_tile(&Vector::new(-1, 2), 1), TileDamageResult::OutOfBounds);
+    }
+
+    #[test]
+    fn test_bump_opens_door() {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        let mut map = Map::empty(5, 5);
+        *map.get_mut(&Vector::new(3, 2)).unwrap() = Tile::of("door_closed");
+
+        world.insert_resource(map).unwrap().insert_resource(BumpEvents::new()).unwrap();
+
+        add_system!(world, MovementSystem::new(), 1010);
+
+        let id = world.insert(Entity::new().insert_component(Position::new(2, 2, 0)).unwrap().insert_component(Actor::new(100)).unwrap().build()).unwrap();
+        world.get_component_mut::<Actor>(&id).unwrap().gain();
+
+        world.add_component(&id, WantsToMove::new(RIGHT_VECTOR)).unwrap();
+        world.tick();
+        world.apply_deferred();
+
+        assert_eq!(world.get_component::<Position>(&id).unwrap().coords(), Vector::new(2, 2));
+        assert!(world.get_resource::<Map>().unwrap().get(&Vector::new(3, 2)).unwrap().walkable());
+        assert!(!world.get_component::<Actor>(&id).unwrap().ready());
+
+        world.add_component(&id, WantsToMove::new(RIGHT_VECTOR)).unwrap();
+        world.tick();
+        world.apply_deferred();
+
+        assert_eq!(world.get_component::<Position>(&id).unwrap().coords(), Vector::new(3, 2));
+    }
+
+    #[test]
+    fn test_moving_lights_invalidate_viewsheds() {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        world.insert_resource(Map::empty(20, 5)).unwrap().insert_resource(LightMap::new(20, 5, 0)).unwrap().insert_resource(TickInfo::new()).unwrap();
+
+        add_system!(world, TickSystem::new(), 1000);
+        add_system!(world, LightingSystem::new(), -850);
+
+        let torch = world.insert(Entity::new().insert_component(Position::new(2, 2, 0)).unwrap().insert_component(LightSource::new(3.5, (255, 255, 255), 255)).unwrap().build()).unwrap();
+        let viewer = world.insert(Entity::new().insert_component(Position::new(10, 2, 0)).unwrap().insert_component(Viewshed::new(5.0)).unwrap().build()).unwrap();
+
+        world.tick();
+        assert!(world.get_resource::<LightMap>().unwrap().lit(&Vector::new(4, 2)));
+
+        world.get_component_mut::<Viewshed>(&viewer).unwrap().update(&mut world.get_resource_mut::<Map>().unwrap(), Vector::new(10, 2), None, false, None);
+        world.get_resource_mut::<TickInfo>().unwrap().request_behaviour_tick();
+        world.tick();
+        assert!(!world.get_component::<Viewshed>(&viewer).unwrap().dirty());
+
+        world.get_component_mut::<Position>(&torch).unwrap().place(Vector::new(12, 2));
+        world.get_resource_mut::<TickInfo>().unwrap().request_behaviour_tick();
+        world.tick();
+
+        let lights = world.get_resource::<LightMap>().unwrap();
+        assert!(!lights.lit(&Vector::new(4, 2)) && lights.lit(&Vector::new(10, 2)));
+        assert!(world.get_component::<Viewshed>(&viewer).unwrap().dirty());
+    }
+
+    #[test]
+    fn test_player_remembers_seen_entities() {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        world.insert_resource(Map::empty(20, 5)).unwrap().insert_resource(TickInfo::new()).unwrap().insert_resource(SpatialIndex::new()).unwrap();
+
+        add_system!(world, TickSystem::new(), 1000);
+        add_system!(world, ViewSystem::new(), -900);
+        add_system!(world, MemorySystem::new(), -950);
+
+        let glyph = rltk::to_cp437('g');
+        let player = world.insert(Entity::new().insert_component(Position::new(1, 2, 255)).unwrap().insert_component(Renderer::new(rltk::to_cp437('@'), None, None)).unwrap().insert_component(Viewshed::new(5.0)).unwrap().insert_component(Player::new()).unwrap().build()).unwrap();
+        let monster = world.insert(Entity::new().insert_component(Position::new(3, 2, 0)).unwrap().insert_component(Renderer::new(glyph, None, None)).unwrap().build()).unwrap();
+
+        world.get_resource_mut::<SpatialIndex>().unwrap().index(&world);
+        world.tick();
+
+        let map = world.get_resource::<Map>().unwrap();
+        assert_eq!(map.remembered(&Vector::new(3, 2)), Some(glyph));
+        assert_eq!(map.remembered(&Vector::new(1, 2)), None);
+        assert_eq!(map.remembered(&Vector::new(-1, 2)), None);
+        drop(map);
+
+        // Once the player walks off the monster is remembered where it was last seen
+        world.get_component_mut::<Position>(&player).unwrap().place(Vector::new(15, 2));
+        world.get_component_mut::<Viewshed>(&player).unwrap().mark_dirty();
+        world.get_resource_mut::<SpatialIndex>().unwrap().move_to(&player, Vector::new(15, 2));
+        world.get_resource_mut::<TickInfo>().unwrap().request_behaviour_tick();
+        world.tick();
+
+        assert_eq!(world.get_resource::<Map>().unwrap().remembered(&Vector::new(3, 2)), Some(glyph));
+
+        // Seeing the tile again without the monster forgets it
+        world.get_component_mut::<Position>(&monster).unwrap().place(Vector::new(0, 0));
+        world.get_resource_mut::<SpatialIndex>().unwrap().move_to(&monster, Vector::new(0, 0));
+        world.get_component_mut::<Position>(&player).unwrap().place(Vector::new(5, 2));
+        world.get_component_mut::<Viewshed>(&player).unwrap().mark_dirty();
+        world.get_resource_mut::<SpatialIndex>().unwrap().move_to(&player, Vector::new(5, 2));
+        world.get_resource_mut::<TickInfo>().unwrap().request_behaviour_tick();
+        world.tick();
+
+        assert_eq!(world.get_resource::<Map>().unwrap().remembered(&Vector::new(3, 2)), None);
+    }
+
+    #[test]
+    fn test_slow_terrain_delays_the_next_turn() {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        let mut map = Map::empty(5, 5);
+        *map.get_mut(&Vector::new(3, 3)).unwrap() = Tile::of("shallow_water");
+
+        world.insert_resource(map).unwrap();
+
+        add_system!(world, MovementSystem::new(), 1010);
+
+        let walker = world.insert(Entity::new().insert_component(Position::new(2, 1, 0)).unwrap().insert_component(Actor::new(100)).unwrap().build()).unwrap();
+        let wader = world.insert(Entity::new().insert_component(Position::new(2, 3, 0)).unwrap().insert_component(Actor::new(100)).unwrap().build()).unwrap();
+
+        for id in [&walker, &wader] {
+            world.get_component_mut::<Actor>(id).unwrap().gain();
+            world.add_component(id, WantsToMove::new(RIGHT_VECTOR)).unwrap();
+        }
+
+        world.tick();
+        world.apply_deferred();
+
+        assert_eq!(world.get_component::<Position>(&wader).unwrap().coords(), Vector::new(3, 3));
+
+        for id in [&walker, &wader] {
+            world.get_component_mut::<Actor>(id).unwrap().gain();
+        }
+
+        assert!(world.get_component::<Actor>(&walker).unwrap().ready());
+        assert!(!world.get_component::<Actor>(&wader).unwrap().ready());
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use serde::Deserialize;
+
+use crate::map::Stairs;
+
+// Tile types the engine places itself, every one of them must be defined in `raws/tiles.json`
+pub const GROUND: &str = "ground";
+pub const WALL: &str = "wall";
+pub const WINDOW: &str = "window";
+pub const STAIRS_UP: &str = "stairs_up";
+pub const STAIRS_DOWN: &str = "stairs_down";
+pub const RUBBLE: &str = "rubble";
+
+static TILE_TYPES: OnceLock<TileTypes> = OnceLock::new();
+
+/// The tile types loaded from `raws/tiles.json`
+pub fn tile_types() -> &'static TileTypes {
+    TILE_TYPES.get_or_init(|| {
+        let types = TileTypes::from_json(crate::RAWS.get_file("tiles.json").unwrap().contents_utf8().unwrap()).unwrap();
+
+        types.verify();
+
+        types
+    })
+}
+
+/// Index into the tile type registry, this is what the map stores per tile
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub struct TileTypeId(u16);
+
+#[derive(Deserialize, PartialEq, Eq, Copy, Clone, Debug)]
+pub enum TileFlag {
+    Liquid,
+    Door,
+    Stairs(Stairs),
+    /// Moving into the tile uses it instead, see `Map::use_tile`
+    UseOnBump,
+}
+
+/// Strength and opaqueness have the same meaning as on `Tile`, which copies them when it is created
+#[derive(Deserialize, Clone, Debug)]
+pub struct TileType {
+    pub name: String,
+    pub glyph: char,
+    pub visible_color: (u8, u8, u8),
+    pub remembered_color: (u8, u8, u8),
+    pub strength: u8,
+    pub opaqueness: u8,
+    #[serde(default = "default_movement_cost")]
+    pub movement_cost: f32,
+    #[serde(default)]
+    pub flags: Vec<TileFlag>,
+    /// The type the tile turns into when its strength reaches 0, plain ground if not given
+    #[serde(default)]
+    pub broken: Option<String>,
+    /// The type the tile switches to when used, a closed door names the open door and the other way around
+    #[serde(default)]
+    pub toggle: Option<String>,
+}
+
+fn default_movement_cost() -> f32 {
+    1.0
+}
+
+impl TileType {
+    pub fn has_flag(&self, flag: TileFlag) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    pub fn liquid(&self) -> bool {
+        self.has_flag(TileFlag::Liquid)
+    }
+
+    pub fn door(&self) -> bool {
+        self.has_flag(TileFlag::Door)
+    }
+
+    pub fn stairs(&self) -> Option<Stairs> {
+        self.flags.iter().find_map(|flag| match flag {
+            TileFlag::Stairs(direction) => Some(*direction),
+            _ => None,
+        })
+    }
+}
+
+pub struct TileTypes {
+    types: Vec<TileType>,
+    ids: HashMap<String, TileTypeId>,
+}
+
+impl TileTypes {
+    pub fn new(types: Vec<TileType>) -> Self {
+        let ids = types.iter().enumerate().map(|(index, tile_type)| (tile_type.name.clone(), TileTypeId(index as u16))).collect();
+
+        TileTypes { types, ids }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        Ok(TileTypes::new(serde_json::from_str(json)?))
+    }
+
+    pub fn verify(&self) {
+        if self.types.len() > u16::MAX as usize {
+            panic!("too many tile types: {}", self.types.len());
+        }
+
+        if self.ids.len() != self.types.len() {
+            panic!("tile type names are not unique");
+        }
+
+        for name in [GROUND, WALL, WINDOW, STAIRS_UP, STAIRS_DOWN, RUBBLE] {
+            if !self.ids.contains_key(name) {
+                panic!("tile types did not contain: {}", name);
+            }
+        }
+
+        for tile_type in self.types.iter() {
+            for other in tile_type.broken.iter().chain(tile_type.toggle.iter()) {
+                if !self.ids.contains_key(other) {
+                    panic!("tile type {} refers to unknown type: {}", tile_type.name, other);
+                }
+            }
+        }
+    }
+
+    pub fn id(&self, name: &str) -> Option<TileTypeId> {
+        self.ids.get(name).copied()
+    }
+
+    /// Panics on unknown ids, ids are only handed out by the registry
+    pub fn get(&self, id: TileTypeId) -> &TileType {
+        &self.types[id.0 as usize]
+    }
+
+    /// The type a tile of the given type becomes once broken
+    pub fn broken(&self, id: TileTypeId) -> TileTypeId {
+        self.get(id).broken.as_deref().and_then(|name| self.id(name)).or_else(|| self.id(GROUND)).unwrap_or(id)
+    }
+
+    /// The type a tile of the given type switches to when used, if it can be used
+    pub fn toggled(&self, id: TileTypeId) -> Option<TileTypeId> {
+        self.id(self.get(id).toggle.as_deref()?)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&TileType> {
+        Some(self.get(self.id(name)?))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TileTypeId, &TileType)> {
+        self.types.iter().enumerate().map(|(index, tile_type)| (TileTypeId(index as u16), tile_type))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::{Stairs, Tile};
+
+    use super::{tile_types, TileFlag};
+
+    #[test]
+    fn test_tile_types_from_raws() {
+        let types = tile_types();
+
+        assert_ne!(Tile::window().tile_type().glyph, Tile::wall().tile_type().glyph);
+        assert!(!Tile::window().walkable() && Tile::window().tile_type().opaqueness == 0);
+        assert_eq!(Tile::stairs(Stairs::Down).get_stairs(), Some(Stairs::Down));
+        assert_eq!(Tile::ground().get_stairs(), None);
+        assert_eq!(Tile::ground().movement_cost(), 1.0);
+
+        let water = types.by_name("shallow_water").unwrap();
+        assert!(water.liquid() && water.has_flag(TileFlag::Liquid) && !water.door());
+        assert!(water.movement_cost > 1.0);
+    }
+}
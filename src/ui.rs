@@ -3,12 +3,13 @@ use std::collections::HashMap;
 use rltk::Rltk;
 use serde::Deserialize;
 
-use crate::{vectors::{Vector, ZERO_VECTOR}, theme::Theme, ecs::{world::World, entity::Entity}, map::Map, query_one, components::{Position, Camera, Renderer, Player, Viewshed}, transform::Transform, query};
+use crate::{dungeon, vectors::{Vector, ZERO_VECTOR, ONE_VECTOR}, theme::Theme, ecs::{world::World, entity::Entity}, map::Map, query, query_one, components::{Position, Camera, Renderer, Player, Viewshed, WantsToMove}, transform::Transform, systems::TurnScheduler, input::parse_input, spatial::SpatialIndex};
 
 #[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
 pub enum UiAction {
     Open { ids: Vec<String> },
     Set { ids: Vec<String> },
+    NewGame { ids: Vec<String> },
     Exit,
 }
 
@@ -101,21 +102,37 @@ impl UiAtomic {
                 }
 
                 // Entity Rendering
-                // First initialize all entity lists to empty vecs
+                // Only entities within the view are considered, the spatial index is shared with the simulation
+                // Entities the player can not see are left out, their tiles show what the map remembers instead
+                // Without a player viewshed nothing is seen and no entity is drawn
+                let query = query!(Player, Viewshed);
+                let player = world.query_one_entity(&query);
+                let viewshed = player.and_then(|player| player.get_component::<Viewshed>());
+
                 let mut entity_map: HashMap<Vector, (Position, &Entity)> = HashMap::new();
 
-                let query = query!(Position, Renderer);
+                let min = camera_transform.apply(position);
+                let max = camera_transform.apply(position + size - ONE_VECTOR);
+
+                if let (Some(index), Some(viewshed)) = (world.get_resource::<SpatialIndex>(), viewshed) {
+                    // Fill the map according to priotity
+                    for (pos, id) in index.within_rect(min, max) {
+                        if !viewshed.contains(&pos) {
+                            continue;
+                        }
+
+                        if let (Some(entity), Some(position)) = (world.get(id), world.get_component::<Position>(id)) {
+                            if !entity.has_component::<Renderer>() {
+                                continue;
+                            }
 
-                // Fill the lists according to priotity
-                for entity in world.query_entities(&query) {
-                    if let Some(position) = entity.get_component::<Position>() {
-                        let pos = position.coords();
-                        if let Some((other_position, _)) = entity_map.get(&pos) {
-                            if position.priority() > other_position.priority() {
+                            if let Some((other_position, _)) = entity_map.get(&pos) {
+                                if position.priority() > other_position.priority() {
+                                    entity_map.insert(pos, (*position, entity));
+                                }
+                            } else {
                                 entity_map.insert(pos, (*position, entity));
                             }
-                        } else {
-                            entity_map.insert(pos, (*position, entity));
                         }
                     }
                 }
@@ -125,7 +142,6 @@ impl UiAtomic {
                     if let Some(renderer) = entity.get_component::<Renderer>() {
                         let screen_pos = camera_transform.inverse_apply(position.coords());
 
-                        // TODO: Fix bounds problem, thats a later hazel problem
                         ctx.set(screen_pos.x, screen_pos.y, renderer.fg().unwrap_or(theme.background_color), renderer.bg().unwrap_or(theme.background_color), renderer.glyph());
                     }
                 }
@@ -161,25 +177,19 @@ impl UiAtomic {
             },
             UiAtomic::Text { text: _text } => (self.clone(), None),
             UiAtomic::WorldView { escape } => {
-                if let (Some(map), Some(player)) = (world.get_resource::<Map>(), query_one!(world, Player)) {
-                    if let Some(mut position) = player.get_component_mut::<Position>() {
-                        let moved = match ctx.key {
-                            None => false,
-                            Some(key) => match key {
-                                rltk::VirtualKeyCode::W | rltk::VirtualKeyCode::Up => (*position).try_move(&*map, Vector::new(0, -1)),
-                                rltk::VirtualKeyCode::A | rltk::VirtualKeyCode::Left => (*position).try_move(&*map, Vector::new(-1, 0)),
-                                rltk::VirtualKeyCode::S | rltk::VirtualKeyCode::Down => (*position).try_move(&*map, Vector::new(0, 1)),
-                                rltk::VirtualKeyCode::D | rltk::VirtualKeyCode::Right => (*position).try_move(&*map, Vector::new(1, 0)),
-                                _ => false
-                            }
-                        };
-        
-                        if let Some(mut viewshed) = player.get_component_mut::<Viewshed>() {
-                            if moved {
-                                (*viewshed).mark_dirty();
-                            }
+                let player_turn = world.get_resource::<TurnScheduler>().is_some_and(|scheduler| scheduler.player_turn());
+
+                if let (true, Some(player), Some(input)) = (player_turn, query_one!(world, Player), parse_input(ctx)) {
+                    if let (Some(delta), Some(id)) = (input.delta(), player.id()) {
+                        if !world.has_component::<WantsToMove>(id) {
+                            let id = id.clone();
+                            world.defer(move |world| { let _ = world.add_component(&id, WantsToMove::new(delta)); });
                         }
                     }
+
+                    if let Some(stairs) = input.stairs() {
+                        world.defer(move |world| { let _ = dungeon::travel(world, stairs); });
+                    }
                 }
 
                 world.tick();
@@ -240,7 +250,7 @@ impl UiPanel {
 
         for component in self.results.values() {
             match component {
-                UiAction::Open { ids } => {
+                UiAction::Open { ids } | UiAction::NewGame { ids } => {
                     for id in ids {
                         references.push(id);
                     }
@@ -13,7 +13,7 @@ pub const RIGHT_VECTOR: Vector = Vector { x: 1, y: 0 };
 pub const UP_VECTOR: Vector = Vector { x: 0, y: -1 };
 pub const DOWN_VECTOR: Vector = Vector { x: 0, y: 1 };
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize, Debug, Default)]
 pub struct Vector {
     pub x: i32,
     pub y: i32,
@@ -108,6 +108,53 @@ impl Vector {
     }
 }
 
+/// A point with integer coordinates along a fixed number of axes
+pub trait Point: Copy + PartialEq {
+    const DIMENSIONS: usize;
+
+    fn component(&self, axis: usize) -> i32;
+}
+
+impl Point for Vector {
+    const DIMENSIONS: usize = 2;
+
+    fn component(&self, axis: usize) -> i32 {
+        if axis == 0 { self.x } else { self.y }
+    }
+}
+
+impl <const N: usize> Point for [i32; N] {
+    const DIMENSIONS: usize = N;
+
+    fn component(&self, axis: usize) -> i32 {
+        self[axis]
+    }
+}
+
+/// How distances between points are measured
+/// Euclidean: Straight line distance, as in `Vector::distance`
+/// Chebyshev: The larger of the axis distances, diagonal steps cost the same as straight ones
+/// Manhattan: The sum of the axis distances, only straight steps are allowed
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Metric {
+    #[default]
+    Euclidean,
+    Chebyshev,
+    Manhattan,
+}
+
+impl Metric {
+    pub fn distance<P: Point>(&self, a: &P, b: &P) -> f32 {
+        let deltas = (0..P::DIMENSIONS).map(|axis| (a.component(axis) as i64 - b.component(axis) as i64).abs());
+
+        match self {
+            Metric::Euclidean => (deltas.map(|delta| delta * delta).sum::<i64>() as f32).sqrt(),
+            Metric::Chebyshev => deltas.max().unwrap_or(0) as f32,
+            Metric::Manhattan => deltas.sum::<i64>() as f32,
+        }
+    }
+}
+
 impl From<(i32, i32)> for Vector {
     fn from(tuple: (i32, i32)) -> Self {
         Vector {